@@ -30,8 +30,12 @@ const fetchApi = async (
 	if (language) params.append("language", language);
 
 	const response = await fetch(`${apiUrl}/api/search?${params.toString()}`);
-	return response.json() as Promise<
-		Array<{
+	const body = (await response.json()) as {
+		query: string;
+		page: number;
+		took_ms: number;
+		cached: boolean;
+		results: Array<{
 			title: string;
 			link: string;
 			snippet: string;
@@ -43,8 +47,9 @@ const fetchApi = async (
 			}> | null;
 			favicon_url: string | null;
 			site_name: string | null;
-		}>
-	>;
+		}>;
+	};
+	return body.results;
 };
 
 const fetchAutocomplete = async (search: string) => {
@@ -0,0 +1,58 @@
+use crate::error::SearchError;
+use fantoccini::wd::Capabilities;
+use fantoccini::ClientBuilder;
+use serde_json::json;
+use std::time::Duration;
+
+// Session creation includes starting the browser, which can take a few seconds
+const SESSION_TIMEOUT: Duration = Duration::from_secs(20);
+const PAGE_TIMEOUT: Duration = Duration::from_secs(60);
+
+// Renders JS-heavy result pages in a remote headless browser (e.g. a
+// selenium/standalone-chrome container), driven over WebDriver by fantoccini
+pub struct HeadlessBrowser {
+    webdriver_url: String,
+}
+
+impl HeadlessBrowser {
+    pub fn new(webdriver_url: &str) -> Self {
+        Self {
+            webdriver_url: webdriver_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    // Load `url` in a fresh browser session and return the rendered DOM
+    pub async fn fetch_rendered_html(&self, url: &str) -> Result<String, SearchError> {
+        let mut builder = ClientBuilder::native();
+        builder.capabilities(capabilities());
+        let client = tokio::time::timeout(SESSION_TIMEOUT, builder.connect(&self.webdriver_url))
+            .await
+            .map_err(|_| SearchError::BrowserError("session creation timed out".to_string()))?
+            .map_err(|e| SearchError::BrowserError(e.to_string()))?;
+
+        // Navigation only returns once the page has loaded
+        let html = tokio::time::timeout(PAGE_TIMEOUT, async {
+            client.goto(url).await?;
+            client.source().await
+        })
+        .await;
+
+        // Always release the session, the browser only has a few slots
+        let _ = client.close().await;
+
+        match html {
+            Ok(html) => html.map_err(|e| SearchError::BrowserError(e.to_string())),
+            Err(_) => Err(SearchError::BrowserError(format!("{} took too long to load", url))),
+        }
+    }
+}
+
+fn capabilities() -> Capabilities {
+    let mut capabilities = Capabilities::new();
+    capabilities.insert("browserName".to_string(), json!("chrome"));
+    capabilities.insert(
+        "goog:chromeOptions".to_string(),
+        json!({ "args": ["--headless=new", "--no-sandbox", "--disable-gpu"] }),
+    );
+    capabilities
+}
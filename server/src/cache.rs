@@ -1,37 +1,209 @@
 use async_trait::async_trait;
-use serde::{de::DeserializeOwned, Serialize};
-use std::time::Duration;
+use regex::{Regex, RegexBuilder};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
 use bb8::Pool;
 use bb8_redis::RedisConnectionManager;
 use bb8::RunError;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::{RwLock, Semaphore, SemaphorePermit};
+use xxhash_rust::xxh3::xxh3_128;
 
+use crate::metrics::SearchMetrics;
+
+// Build a bounded-length cache key: a readable namespace followed by a fixed-length
+// hash of the variable part (query and parameters), so long queries don't make long keys
+pub fn cache_key(namespace: &str, variable: &str) -> String {
+    format!("{}:{:032x}", namespace, xxh3_128(variable.as_bytes()))
+}
+
+// First byte of a stored value, telling how the JSON after it is encoded
+const PLAIN: u8 = 0;
+const GZIP: u8 = 1;
+
+// Gzip the serialized value when it's at least `threshold` bytes and compression actually
+// shrinks it; tiny payloads (short autocomplete lists) only grow from the gzip header
+fn encode_value(json: &str, threshold: usize) -> Vec<u8> {
+    if json.len() >= threshold {
+        let mut encoder = GzEncoder::new(vec![GZIP], Compression::default());
+        if let Ok(compressed) = encoder.write_all(json.as_bytes()).and_then(|_| encoder.finish()) {
+            if compressed.len() <= json.len() {
+                return compressed;
+            }
+        }
+    }
+
+    let mut plain = Vec::with_capacity(json.len() + 1);
+    plain.push(PLAIN);
+    plain.extend_from_slice(json.as_bytes());
+    plain
+}
+
+// Values written before the header byte existed are bare JSON, read as-is
+fn decode_value(bytes: &[u8]) -> Option<String> {
+    match bytes.split_first()? {
+        (&GZIP, compressed) => {
+            let mut json = String::new();
+            GzDecoder::new(compressed).read_to_string(&mut json).ok()?;
+            Some(json)
+        }
+        (&PLAIN, json) => String::from_utf8(json.to_vec()).ok(),
+        _ => String::from_utf8(bytes.to_vec()).ok(),
+    }
+}
+
+// `{"pattern": "\\b(stock|news)\\b", "ttl_secs": 60}`: queries matching `pattern`
+// (case-insensitive) are cached for `ttl_secs`
+#[derive(Debug, Clone, Deserialize)]
+pub struct TtlRule {
+    pub pattern: String,
+    pub ttl_secs: u64,
+}
+
+// Cache lifetime by query pattern, first matching rule wins. Queries matching none use the
+// global TTL.
+#[derive(Debug, Clone, Default)]
+pub struct TtlRules {
+    rules: Vec<(Regex, u64)>,
+}
+
+impl TtlRules {
+    // Invalid patterns and zero TTLs are reported and skipped
+    pub fn new(rules: Vec<TtlRule>) -> Self {
+        let rules = rules
+            .into_iter()
+            .filter_map(|rule| {
+                if rule.ttl_secs == 0 {
+                    eprintln!("TTL rule {} has a zero TTL, skipping it", rule.pattern);
+                    return None;
+                }
+                let pattern = RegexBuilder::new(&rule.pattern)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|e| eprintln!("Invalid TTL rule pattern {}: {}", rule.pattern, e))
+                    .ok()?;
+                Some((pattern, rule.ttl_secs))
+            })
+            .collect();
+
+        Self { rules }
+    }
+
+    pub fn ttl_for(&self, query: &str) -> Option<u64> {
+        self.rules
+            .iter()
+            .find(|(pattern, _)| pattern.is_match(query))
+            .map(|&(_, ttl_secs)| ttl_secs)
+    }
+}
+
+// Storage for serialized (JSON) values. Object safe, so the backend can be picked at startup
+// and shared as `Arc<dyn Cache>`; typed reads and writes go through `CacheExt`.
 #[async_trait]
 pub trait Cache: Send + Sync {
-    async fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T>;
-    async fn set<T: Serialize + Send + Sync>(
+    // `None` when the key is missing or expired
+    async fn get_json(&self, key: &str) -> Option<String>;
+    async fn set_json(
         &self,
         key: &str,
-        value: &T,
+        json: String,
         ttl: Duration,
     ) -> Result<(), redis::RedisError>;
+    async fn delete(&self, key: &str) -> Result<(), redis::RedisError>;
     async fn flush(&self) -> Result<(), redis::RedisError>;
+    // One page of keys matching a glob `pattern` (`*` and `?`), starting at `cursor` (0 for
+    // the first page). Returns the next cursor, 0 once every key has been visited.
+    async fn scan(
+        &self,
+        pattern: &str,
+        cursor: u64,
+    ) -> Result<(u64, Vec<String>), redis::RedisError>;
 }
 
+// Typed access to any `Cache`, values stored as JSON
+#[async_trait]
+pub trait CacheExt: Cache {
+    async fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let json = self.get_json(key).await?;
+        if let Ok(value) = serde_json::from_str(&json) {
+            return Some(value);
+        }
+
+        // Stale-shaped or corrupt: drop it so the next fetch repopulates it instead of failing
+        // on it until the TTL runs out
+        SearchMetrics::record_cache_deserialize_error();
+        let _ = self.delete(key).await;
+        None
+    }
+
+    async fn set<T: Serialize + Send + Sync>(
+        &self,
+        key: &str,
+        value: &T,
+        ttl: Duration,
+    ) -> Result<(), redis::RedisError> {
+        let serialized = serde_json::to_string(value).map_err(|_| {
+            redis::RedisError::from((
+                redis::ErrorKind::InvalidClientConfig,
+                "Serialization failed",
+            ))
+        })?;
+
+        self.set_json(key, serialized, ttl).await
+    }
+}
+
+impl<C: Cache + ?Sized> CacheExt for C {}
+
+// Keys examined per `scan` call. For Redis this is only a hint, so a page can hold more or
+// fewer keys, including none before the scan is over.
+const SCAN_COUNT: usize = 100;
+
 pub struct RedisCache {
     pool: Pool<RedisConnectionManager>,
+    // Values smaller than this many bytes (serialized) are stored uncompressed
+    compression_threshold: usize,
+    // Bounds operations in flight, so a saturated pool queues callers here instead of having
+    // all of them time out waiting for a connection; unbounded when unset
+    permits: Option<Semaphore>,
 }
 
 impl RedisCache {
     pub async fn new(redis_url: &str) -> Result<Self, redis::RedisError> {
         let manager = RedisConnectionManager::new(redis_url)?;
         let pool = Pool::builder().build(manager).await?;
-        Ok(Self { pool })
+        Ok(Self {
+            pool,
+            compression_threshold: 1024,
+            permits: None,
+        })
+    }
+
+    pub fn with_compression_threshold(mut self, compression_threshold: usize) -> Self {
+        self.compression_threshold = compression_threshold;
+        self
+    }
+
+    pub fn with_max_concurrent_ops(mut self, max: Option<usize>) -> Self {
+        self.permits = max.map(|max| Semaphore::new(max.max(1)));
+        self
+    }
+
+    // Wait for a turn when operations are capped; the permit is held for the whole operation
+    async fn permit(&self) -> Option<SemaphorePermit<'_>> {
+        self.permits.as_ref()?.acquire().await.ok()
     }
 }
 
 #[async_trait]
 impl Cache for RedisCache {
-    async fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+    async fn get_json(&self, key: &str) -> Option<String> {
+        let _permit = self.permit().await;
         let mut conn = self.pool.get().await.map_err(|e| match e {
             RunError::User(e) => e,
             RunError::TimedOut => redis::RedisError::from((
@@ -39,21 +211,28 @@ impl Cache for RedisCache {
                 "Connection timed out",
             )),
         }).ok()?;
-        let result: Option<String> = redis::cmd("GET")
+        let result: Option<Vec<u8>> = redis::cmd("GET")
             .arg(key)
             .query_async(&mut *conn)
             .await
             .ok()?;
 
-        result.and_then(|s| serde_json::from_str(&s).ok())
+        let json = decode_value(&result?);
+        if json.is_none() {
+            // Undecodable bytes are as corrupt as bad JSON, see `CacheExt::get`
+            SearchMetrics::record_cache_deserialize_error();
+            let _: Result<(), _> = redis::cmd("DEL").arg(key).query_async(&mut *conn).await;
+        }
+        json
     }
 
-    async fn set<T: Serialize + Send + Sync>(
+    async fn set_json(
         &self,
         key: &str,
-        value: &T,
+        json: String,
         ttl: Duration,
     ) -> Result<(), redis::RedisError> {
+        let _permit = self.permit().await;
         let mut conn = self.pool.get().await.map_err(|e| match e {
             RunError::User(e) => e,
             RunError::TimedOut => redis::RedisError::from((
@@ -61,22 +240,30 @@ impl Cache for RedisCache {
                 "Connection timed out",
             )),
         })?;
-        let serialized = serde_json::to_string(value).map_err(|_| {
-            redis::RedisError::from((
-                redis::ErrorKind::InvalidClientConfig,
-                "Serialization failed",
-            ))
-        })?;
 
-        redis::cmd("SETEX")
+        // Redis rejects a zero expiry, the shortest it takes is a millisecond
+        redis::cmd("PSETEX")
             .arg(key)
-            .arg(ttl.as_secs())
-            .arg(serialized)
+            .arg(ttl.as_millis().max(1) as u64)
+            .arg(encode_value(&json, self.compression_threshold))
             .query_async(&mut *conn)
             .await
     }
 
+    async fn delete(&self, key: &str) -> Result<(), redis::RedisError> {
+        let _permit = self.permit().await;
+        let mut conn = self.pool.get().await.map_err(|e| match e {
+            RunError::User(e) => e,
+            RunError::TimedOut => redis::RedisError::from((
+                redis::ErrorKind::IoError,
+                "Connection timed out",
+            )),
+        })?;
+        redis::cmd("DEL").arg(key).query_async(&mut *conn).await
+    }
+
     async fn flush(&self) -> Result<(), redis::RedisError> {
+        let _permit = self.permit().await;
         let mut conn = self.pool.get().await.map_err(|e| match e {
             RunError::User(e) => e,
             RunError::TimedOut => redis::RedisError::from((
@@ -86,4 +273,134 @@ impl Cache for RedisCache {
         })?;
         redis::cmd("FLUSHDB").query_async(&mut *conn).await
     }
+
+    async fn scan(
+        &self,
+        pattern: &str,
+        cursor: u64,
+    ) -> Result<(u64, Vec<String>), redis::RedisError> {
+        let _permit = self.permit().await;
+        let mut conn = self.pool.get().await.map_err(|e| match e {
+            RunError::User(e) => e,
+            RunError::TimedOut => redis::RedisError::from((
+                redis::ErrorKind::IoError,
+                "Connection timed out",
+            )),
+        })?;
+        redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(pattern)
+            .arg("COUNT")
+            .arg(SCAN_COUNT)
+            .query_async(&mut *conn)
+            .await
+    }
+}
+
+// Process-local cache for tests and single-instance setups without Redis. Values are stored
+// serialized, like in Redis, so reading one back as the wrong type behaves the same. Hits
+// only take the read lock, so concurrent ones don't wait on each other.
+#[derive(Default)]
+pub struct InMemoryCache {
+    entries: RwLock<HashMap<String, (String, Instant)>>,
+    // Writes since the last sweep of expired entries
+    inserts: AtomicUsize,
+}
+
+// Expired entries are dropped when read, and swept once every this many writes for the ones
+// never read again. Sweeping scans the whole map, so doing it on every write would make each
+// write O(n) under the lock.
+const SWEEP_EVERY: usize = 1024;
+
+impl InMemoryCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl Cache for InMemoryCache {
+    async fn get_json(&self, key: &str) -> Option<String> {
+        let now = Instant::now();
+        {
+            let entries = self.entries.read().await;
+            let (json, expires_at) = entries.get(key)?;
+            if *expires_at > now {
+                return Some(json.clone());
+            }
+        }
+
+        // Expired: evict it, unless it was replaced while the lock was released
+        let mut entries = self.entries.write().await;
+        if entries.get(key).is_some_and(|(_, expires_at)| *expires_at <= now) {
+            entries.remove(key);
+        }
+        None
+    }
+
+    async fn set_json(
+        &self,
+        key: &str,
+        json: String,
+        ttl: Duration,
+    ) -> Result<(), redis::RedisError> {
+        let now = Instant::now();
+        let mut entries = self.entries.write().await;
+        if self.inserts.fetch_add(1, Ordering::Relaxed) % SWEEP_EVERY == SWEEP_EVERY - 1 {
+            entries.retain(|_, (_, expires_at)| *expires_at > now);
+        }
+        entries.insert(key.to_string(), (json, now + ttl));
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), redis::RedisError> {
+        self.entries.write().await.remove(key);
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<(), redis::RedisError> {
+        self.entries.write().await.clear();
+        Ok(())
+    }
+
+    // The cursor is an offset into the live keys in sorted order, so keys added or expiring
+    // between calls can shift a page, much like Redis makes no promise about them either
+    async fn scan(
+        &self,
+        pattern: &str,
+        cursor: u64,
+    ) -> Result<(u64, Vec<String>), redis::RedisError> {
+        let now = Instant::now();
+        let entries = self.entries.read().await;
+        let mut keys: Vec<&String> = entries
+            .iter()
+            .filter(|(_, (_, expires_at))| *expires_at > now)
+            .map(|(key, _)| key)
+            .collect();
+        keys.sort();
+
+        let start = (cursor as usize).min(keys.len());
+        let end = (start + SCAN_COUNT).min(keys.len());
+        let page = keys[start..end]
+            .iter()
+            .filter(|key| glob_match(pattern.as_bytes(), key.as_bytes()))
+            .map(|key| key.to_string())
+            .collect();
+        let next = if end == keys.len() { 0 } else { end as u64 };
+
+        Ok((next, page))
+    }
+}
+
+// Redis-style glob with `*` (any run of characters) and `?` (any single one)
+fn glob_match(pattern: &[u8], key: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => key.is_empty(),
+        Some((b'*', rest)) => (0..=key.len()).any(|skip| glob_match(rest, &key[skip..])),
+        Some((&c, rest)) => match key.split_first() {
+            Some((&k, key)) if c == b'?' || c == k => glob_match(rest, key),
+            _ => false,
+        },
+    }
 }
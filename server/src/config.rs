@@ -0,0 +1,633 @@
+use crate::cache::TtlRules;
+use crate::intent::IntentClassifier;
+use crate::json_api::JsonApiConfig;
+use crate::query::{Blocklist, Synonyms};
+use crate::rate_limiter::RateWindow;
+use crate::scoring::{
+    DedupPreference, DedupScope, DuplicateTitle, EngineWeight, KindRule, LowValuePatterns,
+    MergeMode, ScoringConfig, TitleCopies,
+};
+use crate::scraper::FaviconProvider;
+use crate::telemetry::{MetricsBackend, QueryLogging};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::str::FromStr;
+
+// What `Cache-Control` header search responses carry
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CacheControlPolicy {
+    // `max-age` set to the remaining cache TTL, `no-store` for personalized or stale results
+    #[default]
+    Auto,
+    // Always `no-store`
+    NoStore,
+    // No header at all
+    Off,
+}
+
+impl FromStr for CacheControlPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "auto" => Ok(CacheControlPolicy::Auto),
+            "no-store" => Ok(CacheControlPolicy::NoStore),
+            "off" => Ok(CacheControlPolicy::Off),
+            other => Err(format!("unknown cache-control policy: {}", other)),
+        }
+    }
+}
+
+// Deployment-wide search parameters, each overridden by the request's own value
+#[derive(Debug, Clone, Default)]
+pub struct DefaultSearchParams {
+    // Region and language only apply when the client sends none and GeoIP can't tell
+    pub region: Option<String>,
+    pub language: Option<String>,
+    pub date_range: Option<String>,
+    pub scoring: Option<bool>,
+}
+
+// Server-wide settings, read once from the environment at startup
+#[derive(Debug, Clone)]
+pub struct Config {
+    // Highest page a client may request
+    pub max_page: u32,
+    // Clamp pages above `max_page` instead of rejecting the request with a 400
+    pub clamp_page: bool,
+    // Leave Google's sponsored results out; when off, they're unwrapped to their landing page
+    pub drop_ads: bool,
+    // Searches allowed per client IP and minute, answered with a 429 beyond that; 0 disables
+    pub client_rate_limit: u32,
+    // Identify clients by the last `X-Forwarded-For` entry, the one our reverse proxy appends.
+    // Only enable behind such a proxy, clients can send the header themselves.
+    pub trust_forwarded_for: bool,
+    // Search parameters requests inherit when they don't set them
+    pub search_defaults: DefaultSearchParams,
+    // MaxMind database used to guess region/language from the client IP
+    pub geoip_db_path: Option<String>,
+    // Domains (and their subdomains) whose `http://` result links are rewritten to `https://`,
+    // from `HTTPS_UPGRADE_DOMAINS=wikipedia.org,github.com`. Others are left alone, since some
+    // sites only serve HTTP.
+    pub https_upgrade_domains: Vec<String>,
+    // Rules tagging results with a `kind` (`video`, `code`, ...), first match wins. Loaded from
+    // the JSON array of `KindRule`s in `RESULT_KIND_RULES_FILE`, which replaces the built-in set.
+    pub result_kind_rules: Vec<KindRule>,
+    // Drop results whose snippet is shorter than this (homepages excepted); disabled when unset
+    pub min_snippet_length: Option<usize>,
+    // Drop results whose trimmed title has fewer characters than this, usually parsing
+    // artifacts from malformed HTML; the default of 1 only drops empty titles
+    pub min_title_length: usize,
+    // Extra JSON API engines, loaded from the file named by `JSON_API_ENGINES_FILE`
+    pub json_api_engines: Vec<JsonApiConfig>,
+    // Google Custom Search JSON API credentials; the engine is enabled when both are set
+    pub google_api_key: Option<String>,
+    pub google_cse_id: Option<String>,
+    // Rules routing queries to engines by intent (e.g. code queries to Google only), loaded
+    // from the JSON array of `IntentRule`s in `INTENT_RULES_FILE`; every query goes to every
+    // engine when unset
+    pub intent_classifier: IntentClassifier,
+    // Attempts per engine the success rate in `/api/engines` is computed over
+    pub engine_health_window: usize,
+    // Opt-in query expansion: synonyms from the JSON object of `"word": [synonyms]` in
+    // `SYNONYMS_FILE`, at most `MAX_SYNONYMS` per query, are OR-ed into the query for engines
+    // that understand `OR`. Scoring still uses the original terms.
+    pub synonyms: Synonyms,
+    // Cap on engines queried at the same time; unlimited when unset
+    pub max_concurrent_engines: Option<usize>,
+    // Cap on outgoing scrape requests (engine fetches and favicon checks) across all searches
+    pub max_concurrent_scrapes: usize,
+    // Icon service for results without their own favicon (`google`, `duckduckgo` or `none`)
+    pub favicon_provider: FaviconProvider,
+    // `verify_favicons=true` searches HEAD-check each favicon URL, at most this many at a time
+    // per search (within `max_concurrent_scrapes` overall) and each for at most
+    // `favicon_check_timeout_ms`; a timeout keeps the favicon
+    pub favicon_check_concurrency: usize,
+    pub favicon_check_timeout_ms: u64,
+    // Remote WebDriver endpoint used to render JS-only result pages
+    pub headless_browser_url: Option<String>,
+    // How engine results are combined into the final ranking
+    pub merge_mode: MergeMode,
+    // Per-engine weight and bonus for `MergeMode::Weighted`,
+    // from `ENGINE_WEIGHTS=Google:1.2:0.1,DuckDuckGo:0.9`
+    pub engine_weights: HashMap<String, EngineWeight>,
+    // Longest search deadline a client may ask for with `X-Request-Timeout-Ms`
+    pub max_request_timeout_ms: u64,
+    // Upper bound on the upstream autocomplete request
+    pub autocomplete_timeout_ms: u64,
+    // Suggestions kept per query, applied before caching to bound Redis memory. Fresh fetches
+    // are cut the same way so cached and uncached responses match; there's no separate
+    // read-time limit, so this is also the most a client ever gets from upstream (its own
+    // history entries come on top).
+    pub autocomplete_cache_limit: usize,
+    // Longest autocomplete query, in characters, sent upstream and cached
+    pub autocomplete_max_query_length: usize,
+    // Truncate longer autocomplete queries instead of rejecting them with a 400
+    pub truncate_autocomplete_query: bool,
+    // Serve the last good copy of the results, flagged `stale`, when a fresh fetch fails and
+    // leaves nothing to show. Off by default: it writes a second, long-lived copy of every
+    // search
+    pub serve_stale_on_failure: bool,
+    // How long that copy is kept around after the regular cache entry expires
+    pub stale_retention_secs: u64,
+    // Grace window after a cache entry expires during which its last copy is still served,
+    // flagged `stale`, when some engines fail and the others find nothing. Independent of
+    // `serve_stale_on_failure`, which only covers every engine failing; 0 turns it off
+    pub stale_grace_secs: u64,
+    // Clean up whitespace and stray quotes before searching (see `query::normalize_query`)
+    pub normalize_query: bool,
+    // Engine whose copy wins when deduplicating near-equal results
+    pub dedup_preference: Option<DedupPreference>,
+    // Whether duplicates collapse across engines (`global`) or only within one (`per_engine`)
+    pub dedup_scope: DedupScope,
+    // Keep links to different fragments of one page (`#install` vs `#usage`) as separate
+    // results; by default fragments are ignored and such links are duplicates
+    pub fragment_sensitive_dedup: bool,
+    // Title kept when duplicates were titled differently: `query_terms` (most query terms
+    // matched), `longest`, or `engine:<name>` for that engine's title
+    pub duplicate_title: DuplicateTitle,
+    // Same-title results on different domains: `merge` them, or `penalize` all but the
+    // authoritative copy by multiplying their score by `title_copy_penalty`. Unscored searches
+    // (`scoring=false`) keep the engines' order, so their copies are kept but not penalized.
+    pub title_copies: TitleCopies,
+    pub title_copy_penalty: f64,
+    // Weights and site/word lists of the heuristic score, from the JSON object in
+    // `SCORING_CONFIG_FILE` (missing fields keep their defaults), then the per-weight variables.
+    // Low-value patterns come from `LOW_VALUE_PATTERNS_FILE`, holding
+    // `{"titles": [regex...], "paths": [regex...]}`, which replaces the built-in set.
+    pub scoring: ScoringConfig,
+    // Return each result's heuristic score as `raw_score`, before engine weights and penalties
+    pub expose_raw_score: bool,
+    // Upper bound on the serialized results of one search; the lowest-ranked ones are dropped
+    // to fit
+    pub max_response_bytes: usize,
+    // Drop an engine's page > 1 when it's a repeat of its first page, and report the engine as
+    // out of pages instead
+    pub detect_repeated_pages: bool,
+    // Retry once an engine whose page comes back empty right after a page with results, to
+    // tell a temporary block from the end of its results
+    pub retry_empty_pages: bool,
+    // How long search results stay cached
+    pub cache_ttl_secs: u64,
+    // Per-query-pattern TTLs overriding `cache_ttl_secs` (short for news, long for
+    // definitions), from the JSON array of `{"pattern", "ttl_secs"}` in `CACHE_TTL_RULES_FILE`
+    pub cache_ttl_rules: TtlRules,
+    // Cached values smaller than this (in bytes, serialized) are stored without gzip
+    pub cache_compression_threshold: usize,
+    // Redis operations allowed in flight at once; the rest wait their turn. Useful when the
+    // connection pool is small next to the load. Unlimited when unset.
+    pub redis_max_concurrent_ops: Option<usize>,
+    // Engines that must answer for results to be cached (0 caches even empty outcomes)
+    pub min_engines_to_cache: usize,
+    pub cache_control: CacheControlPolicy,
+    // Extra attempts an engine gets per query after a failure, from `ENGINE_RETRY_BUDGETS=Google:1`;
+    // engines not listed aren't retried
+    pub retry_budgets: HashMap<String, u32>,
+    // Random pause in milliseconds before each request to an engine, so hits don't land at
+    // machine-regular intervals: `ENGINE_POLITENESS_DELAYS=Google:100-500,DuckDuckGo:50`.
+    // Engines not listed go out immediately.
+    pub politeness_delays: HashMap<String, (u64, u64)>,
+    // Time windows scaling every engine's rate limit, in local time:
+    // `RATE_SCHEDULE=08:00-20:00=0.5` halves it during the day. Unscaled when unset.
+    pub rate_schedule: Vec<RateWindow>,
+    // `Referer` sent to each engine, for those that block requests without a plausible one:
+    // `ENGINE_REFERERS=Google:https://www.google.com/`. Engines not listed send none.
+    pub engine_referers: HashMap<String, String>,
+    // Autocomplete suggestions to drop, loaded from the JSON array of substrings and `/regex/`
+    // entries in `AUTOCOMPLETE_BLOCKLIST_FILE`; nothing is filtered when unset
+    pub autocomplete_blocklist: Blocklist,
+    // Recent queries remembered per client (`X-Client-Id`) and offered first in autocomplete;
+    // disabled when unset
+    pub query_history_size: Option<usize>,
+    pub query_history_ttl_secs: u64,
+    // Reorder upstream suggestions by similarity to the typed query instead of keeping
+    // Google's order (`rank_suggestions`)
+    pub fuzzy_autocomplete: bool,
+    // Cap on merged results before scoring, shared between engines in proportion to what each
+    // returned. Saves CPU on huge result sets, but results beyond an engine's share are never
+    // scored, so a strong match deep in one engine's list can be lost. Unlimited when unset.
+    pub max_candidates: Option<usize>,
+    // Results each responding engine is guaranteed within the first `engine_floor_window`
+    // positions; no guarantee when unset
+    pub engine_result_floor: Option<usize>,
+    pub engine_floor_window: usize,
+    // JSON file of `"query": [results]` loaded into the cache at startup, for demos and
+    // offline testing
+    pub preload_cache_file: Option<String>,
+    pub preload_cache_ttl_secs: u64,
+    // Token for admin/debug endpoints (e.g. `/api/compare`); they're disabled when unset
+    pub admin_token: Option<String>,
+    // Metrics/tracing export (`none`, `otlp` or `prometheus`) and the OTLP collector's gRPC
+    // endpoint
+    pub metrics_backend: MetricsBackend,
+    pub otlp_endpoint: String,
+    // How queries are recorded in request spans (`plain`, `hashed` or `off`). Clients can
+    // opt out per request with `X-Do-Not-Log: 1`, but can't opt back in past this setting.
+    pub query_logging: QueryLogging,
+    // Tokio worker threads; one per core when unset
+    pub worker_threads: Option<usize>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            max_page: 10,
+            clamp_page: false,
+            drop_ads: true,
+            client_rate_limit: 30,
+            trust_forwarded_for: false,
+            search_defaults: DefaultSearchParams::default(),
+            geoip_db_path: None,
+            https_upgrade_domains: [
+                "wikipedia.org",
+                "wikimedia.org",
+                "github.com",
+                "stackoverflow.com",
+                "youtube.com",
+                "reddit.com",
+                "mozilla.org",
+                "google.com",
+            ]
+            .map(String::from)
+            .to_vec(),
+            result_kind_rules: KindRule::defaults(),
+            min_snippet_length: None,
+            min_title_length: 1,
+            json_api_engines: Vec::new(),
+            google_api_key: None,
+            google_cse_id: None,
+            intent_classifier: IntentClassifier::default(),
+            engine_health_window: 100,
+            synonyms: Synonyms::default(),
+            max_concurrent_engines: None,
+            max_concurrent_scrapes: 32,
+            favicon_provider: FaviconProvider::default(),
+            favicon_check_concurrency: 4,
+            favicon_check_timeout_ms: 1000,
+            headless_browser_url: None,
+            merge_mode: MergeMode::default(),
+            engine_weights: HashMap::new(),
+            max_request_timeout_ms: 10000,
+            autocomplete_timeout_ms: 2000,
+            autocomplete_cache_limit: 20,
+            autocomplete_max_query_length: 100,
+            truncate_autocomplete_query: false,
+            serve_stale_on_failure: false,
+            stale_retention_secs: 86400,
+            stale_grace_secs: 0,
+            normalize_query: true,
+            dedup_preference: None,
+            dedup_scope: DedupScope::default(),
+            fragment_sensitive_dedup: false,
+            duplicate_title: DuplicateTitle::default(),
+            title_copies: TitleCopies::default(),
+            title_copy_penalty: 0.5,
+            scoring: ScoringConfig::default(),
+            expose_raw_score: false,
+            max_response_bytes: 1024 * 1024,
+            detect_repeated_pages: true,
+            retry_empty_pages: false,
+            cache_ttl_secs: 300,
+            cache_ttl_rules: TtlRules::default(),
+            cache_compression_threshold: 1024,
+            redis_max_concurrent_ops: None,
+            min_engines_to_cache: 0,
+            cache_control: CacheControlPolicy::default(),
+            retry_budgets: HashMap::new(),
+            politeness_delays: HashMap::new(),
+            rate_schedule: Vec::new(),
+            engine_referers: HashMap::new(),
+            autocomplete_blocklist: Blocklist::default(),
+            query_history_size: None,
+            query_history_ttl_secs: 604800,
+            fuzzy_autocomplete: false,
+            max_candidates: None,
+            engine_result_floor: None,
+            engine_floor_window: 10,
+            preload_cache_file: None,
+            preload_cache_ttl_secs: 30 * 86400,
+            admin_token: None,
+            metrics_backend: MetricsBackend::default(),
+            otlp_endpoint: "http://localhost:4317".to_string(),
+            query_logging: QueryLogging::default(),
+            worker_threads: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        let scoring: ScoringConfig = env_opt("SCORING_CONFIG_FILE")
+            .and_then(|path| load_json_file(&path))
+            .unwrap_or(default.scoring);
+
+        Self {
+            max_page: env_or("MAX_PAGE", default.max_page),
+            clamp_page: env_or("CLAMP_PAGE", default.clamp_page),
+            drop_ads: env_or("DROP_ADS", default.drop_ads),
+            client_rate_limit: env_or("CLIENT_RATE_LIMIT", default.client_rate_limit),
+            trust_forwarded_for: env_or("TRUST_FORWARDED_FOR", default.trust_forwarded_for),
+            search_defaults: DefaultSearchParams {
+                region: env_opt("DEFAULT_REGION"),
+                language: env_opt("DEFAULT_LANGUAGE"),
+                date_range: env_opt("DEFAULT_DATE_RANGE"),
+                scoring: env_opt("DEFAULT_SCORING").and_then(|v| v.parse().ok()),
+            },
+            geoip_db_path: env_opt("GEOIP_DB_PATH"),
+            https_upgrade_domains: env_opt("HTTPS_UPGRADE_DOMAINS")
+                .map(|value| {
+                    value
+                        .split(',')
+                        .map(|domain| domain.trim().trim_start_matches('.').to_ascii_lowercase())
+                        .filter(|domain| !domain.is_empty())
+                        .collect()
+                })
+                .unwrap_or(default.https_upgrade_domains),
+            result_kind_rules: env_opt("RESULT_KIND_RULES_FILE")
+                .and_then(|path| load_json_file(&path))
+                .unwrap_or(default.result_kind_rules),
+            min_snippet_length: env_opt("MIN_SNIPPET_LENGTH").and_then(|v| v.parse().ok()),
+            min_title_length: env_or("MIN_TITLE_LENGTH", default.min_title_length),
+            json_api_engines: env_opt("JSON_API_ENGINES_FILE")
+                .map(|path| load_json_file(&path).unwrap_or_default())
+                .unwrap_or_default(),
+            google_api_key: env_opt("GOOGLE_API_KEY"),
+            google_cse_id: env_opt("GOOGLE_CSE_ID"),
+            intent_classifier: env_opt("INTENT_RULES_FILE")
+                .and_then(|path| load_json_file(&path))
+                .map(IntentClassifier::new)
+                .unwrap_or_default(),
+            engine_health_window: env_or("ENGINE_HEALTH_WINDOW", default.engine_health_window),
+            synonyms: env_opt("SYNONYMS_FILE")
+                .and_then(|path| load_json_file(&path))
+                .map(|map| Synonyms::new(map, env_or("MAX_SYNONYMS", 3)))
+                .unwrap_or_default(),
+            max_concurrent_engines: env_opt("MAX_CONCURRENT_ENGINES")
+                .and_then(|v| v.parse().ok()),
+            max_concurrent_scrapes: env_or(
+                "MAX_CONCURRENT_SCRAPES",
+                default.max_concurrent_scrapes,
+            ),
+            favicon_provider: env_or("FAVICON_PROVIDER", default.favicon_provider),
+            favicon_check_concurrency: env_or(
+                "FAVICON_CHECK_CONCURRENCY",
+                default.favicon_check_concurrency,
+            ),
+            favicon_check_timeout_ms: env_or(
+                "FAVICON_CHECK_TIMEOUT_MS",
+                default.favicon_check_timeout_ms,
+            ),
+            headless_browser_url: env_opt("HEADLESS_BROWSER_URL"),
+            merge_mode: env_or("MERGE_MODE", default.merge_mode),
+            engine_weights: env_opt("ENGINE_WEIGHTS")
+                .map(|value| parse_engine_weights(&value))
+                .unwrap_or_default(),
+            max_request_timeout_ms: env_or(
+                "MAX_REQUEST_TIMEOUT_MS",
+                default.max_request_timeout_ms,
+            ),
+            autocomplete_timeout_ms: env_or(
+                "AUTOCOMPLETE_TIMEOUT_MS",
+                default.autocomplete_timeout_ms,
+            ),
+            autocomplete_cache_limit: env_or(
+                "AUTOCOMPLETE_CACHE_LIMIT",
+                default.autocomplete_cache_limit,
+            ),
+            autocomplete_max_query_length: env_or(
+                "AUTOCOMPLETE_MAX_QUERY_LENGTH",
+                default.autocomplete_max_query_length,
+            ),
+            truncate_autocomplete_query: env_or(
+                "TRUNCATE_AUTOCOMPLETE_QUERY",
+                default.truncate_autocomplete_query,
+            ),
+            serve_stale_on_failure: env_or(
+                "SERVE_STALE_ON_FAILURE",
+                default.serve_stale_on_failure,
+            ),
+            stale_retention_secs: env_or("STALE_RETENTION_SECS", default.stale_retention_secs),
+            stale_grace_secs: env_or("STALE_GRACE_SECS", default.stale_grace_secs),
+            normalize_query: env_or("NORMALIZE_QUERY", default.normalize_query),
+            dedup_preference: env_opt("DEDUP_PREFERRED_ENGINE").map(|engine| DedupPreference {
+                engine,
+                margin: env_or("DEDUP_PREFERENCE_MARGIN", 0.1),
+            }),
+            dedup_scope: env_or("DEDUP_SCOPE", default.dedup_scope),
+            fragment_sensitive_dedup: env_or(
+                "FRAGMENT_SENSITIVE_DEDUP",
+                default.fragment_sensitive_dedup,
+            ),
+            duplicate_title: env_or("DUPLICATE_TITLE", default.duplicate_title),
+            title_copies: env_or("TITLE_COPIES", default.title_copies),
+            title_copy_penalty: env_or("TITLE_COPY_PENALTY", default.title_copy_penalty),
+            scoring: ScoringConfig {
+                domain_match_bonus: env_or("DOMAIN_MATCH_BONUS", scoring.domain_match_bonus),
+                snippet_density_weight: env_or(
+                    "SNIPPET_DENSITY_WEIGHT",
+                    scoring.snippet_density_weight,
+                ),
+                position_weight: env_or("POSITION_WEIGHT", scoring.position_weight),
+                low_value: load_low_value_patterns(scoring.low_value),
+                idf_coverage: env_or("IDF_COVERAGE", scoring.idf_coverage),
+                metadata_bonus: env_or("METADATA_BONUS", scoring.metadata_bonus),
+                age_decay: env_or("AGE_DECAY", scoring.age_decay),
+                ..scoring
+            },
+            expose_raw_score: env_or("EXPOSE_RAW_SCORE", default.expose_raw_score),
+            max_response_bytes: env_or("MAX_RESPONSE_BYTES", default.max_response_bytes),
+            detect_repeated_pages: env_or("DETECT_REPEATED_PAGES", default.detect_repeated_pages),
+            retry_empty_pages: env_or("RETRY_EMPTY_PAGES", default.retry_empty_pages),
+            cache_ttl_secs: env_or("CACHE_TTL_SECS", default.cache_ttl_secs),
+            cache_ttl_rules: env_opt("CACHE_TTL_RULES_FILE")
+                .and_then(|path| load_json_file(&path))
+                .map(TtlRules::new)
+                .unwrap_or_default(),
+            cache_compression_threshold: env_or(
+                "CACHE_COMPRESSION_THRESHOLD",
+                default.cache_compression_threshold,
+            ),
+            redis_max_concurrent_ops: env_opt("REDIS_MAX_CONCURRENT_OPS")
+                .and_then(|v| v.parse().ok()),
+            min_engines_to_cache: env_or("MIN_ENGINES_TO_CACHE", default.min_engines_to_cache),
+            cache_control: env_or("CACHE_CONTROL", default.cache_control),
+            retry_budgets: env_opt("ENGINE_RETRY_BUDGETS")
+                .map(|value| parse_retry_budgets(&value))
+                .unwrap_or_default(),
+            politeness_delays: env_opt("ENGINE_POLITENESS_DELAYS")
+                .map(|value| parse_politeness_delays(&value))
+                .unwrap_or_default(),
+            rate_schedule: env_opt("RATE_SCHEDULE")
+                .map(|value| parse_rate_schedule(&value))
+                .unwrap_or_default(),
+            engine_referers: env_opt("ENGINE_REFERERS")
+                .map(|value| parse_engine_referers(&value))
+                .unwrap_or_default(),
+            autocomplete_blocklist: env_opt("AUTOCOMPLETE_BLOCKLIST_FILE")
+                .and_then(|path| load_json_file::<Vec<String>>(&path))
+                .map(|entries| Blocklist::from_entries(&entries))
+                .unwrap_or_default(),
+            query_history_size: env_opt("QUERY_HISTORY_SIZE")
+                .and_then(|v| v.parse().ok())
+                .filter(|&size| size > 0),
+            query_history_ttl_secs: env_or(
+                "QUERY_HISTORY_TTL_SECS",
+                default.query_history_ttl_secs,
+            ),
+            fuzzy_autocomplete: env_or("FUZZY_AUTOCOMPLETE", default.fuzzy_autocomplete),
+            max_candidates: env_opt("MAX_CANDIDATES")
+                .and_then(|v| v.parse().ok())
+                .filter(|&max| max > 0),
+            engine_result_floor: env_opt("ENGINE_RESULT_FLOOR").and_then(|v| v.parse().ok()),
+            engine_floor_window: env_or("ENGINE_FLOOR_WINDOW", default.engine_floor_window),
+            preload_cache_file: env_opt("PRELOAD_CACHE_FILE"),
+            preload_cache_ttl_secs: env_or(
+                "PRELOAD_CACHE_TTL_SECS",
+                default.preload_cache_ttl_secs,
+            ),
+            admin_token: env_opt("ADMIN_TOKEN"),
+            metrics_backend: env_or("METRICS_BACKEND", default.metrics_backend),
+            otlp_endpoint: env_opt("OTEL_EXPORTER_OTLP_ENDPOINT").unwrap_or(default.otlp_endpoint),
+            query_logging: env_or("LOG_QUERIES", default.query_logging),
+            worker_threads: env_opt("TOKIO_WORKER_THREADS")
+                .and_then(|v| v.parse().ok())
+                .filter(|&threads| threads > 0),
+        }
+    }
+}
+
+// Read and parse an environment variable, falling back to `default` when unset or invalid
+fn env_or<T: FromStr>(key: &str, default: T) -> T {
+    env::var(key)
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(default)
+}
+
+// Read an optional string setting, treating empty values as unset
+fn env_opt(key: &str) -> Option<String> {
+    env::var(key)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+// Parse `Engine:weight[:bonus]` entries separated by commas, skipping malformed ones
+fn parse_engine_weights(value: &str) -> HashMap<String, EngineWeight> {
+    value
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(':').map(str::trim);
+            let engine = parts.next().filter(|name| !name.is_empty())?;
+            let weight = parts.next()?.parse().ok()?;
+            let priority_bonus = match parts.next() {
+                Some(bonus) => bonus.parse().ok()?,
+                None => 0.0,
+            };
+
+            Some((
+                engine.to_string(),
+                EngineWeight {
+                    weight,
+                    priority_bonus,
+                },
+            ))
+        })
+        .collect()
+}
+
+// Parse `Engine:attempts` entries separated by commas, skipping malformed ones
+fn parse_retry_budgets(value: &str) -> HashMap<String, u32> {
+    value
+        .split(',')
+        .filter_map(|entry| {
+            let (engine, budget) = entry.split_once(':')?;
+            let engine = engine.trim();
+            if engine.is_empty() {
+                return None;
+            }
+
+            Some((engine.to_string(), budget.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+// Parse comma-separated rate windows, reporting and skipping invalid ones
+fn parse_rate_schedule(value: &str) -> Vec<RateWindow> {
+    value
+        .split(',')
+        .filter(|window| !window.trim().is_empty())
+        .filter_map(|window| window.parse().map_err(|e| eprintln!("{}", e)).ok())
+        .collect()
+}
+
+// Parse `Engine:url` entries separated by commas, skipping malformed ones
+fn parse_engine_referers(value: &str) -> HashMap<String, String> {
+    value
+        .split(',')
+        .filter_map(|entry| {
+            let (engine, referer) = entry.split_once(':')?;
+            let (engine, referer) = (engine.trim(), referer.trim());
+            if engine.is_empty() || referer.is_empty() {
+                return None;
+            }
+
+            Some((engine.to_string(), referer.to_string()))
+        })
+        .collect()
+}
+
+// Parse `Engine:min-max` (or `Engine:ms` for a fixed delay) entries separated by commas,
+// skipping malformed ones
+fn parse_politeness_delays(value: &str) -> HashMap<String, (u64, u64)> {
+    value
+        .split(',')
+        .filter_map(|entry| {
+            let (engine, range) = entry.split_once(':')?;
+            let engine = engine.trim();
+            if engine.is_empty() {
+                return None;
+            }
+
+            let (min, max) = range.split_once('-').unwrap_or((range, range));
+            let (min, max) = (min.trim().parse().ok()?, max.trim().parse().ok()?);
+            (min <= max).then(|| (engine.to_string(), (min, max)))
+        })
+        .collect()
+}
+
+fn load_low_value_patterns(default: LowValuePatterns) -> LowValuePatterns {
+    let penalty = env_or("LOW_VALUE_PENALTY", default.penalty);
+
+    match env_opt("LOW_VALUE_PATTERNS_FILE")
+        .and_then(|path| load_json_file::<HashMap<String, Vec<String>>>(&path))
+    {
+        Some(patterns) => LowValuePatterns::new(
+            patterns.get("titles").map(Vec::as_slice).unwrap_or_default(),
+            patterns.get("paths").map(Vec::as_slice).unwrap_or_default(),
+            penalty,
+        ),
+        None => LowValuePatterns { penalty, ..default },
+    }
+}
+
+// Load a JSON settings file, reporting (but not failing on) missing or malformed files
+fn load_json_file<T: serde::de::DeserializeOwned>(path: &str) -> Option<T> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", path, e);
+            return None;
+        }
+    };
+
+    match serde_json::from_str(&content) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            eprintln!("Failed to parse {}: {}", path, e);
+            None
+        }
+    }
+}
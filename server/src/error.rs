@@ -8,6 +8,13 @@ pub enum SearchError {
     #[error("Parsing failed: {0}")]
     ParsingError(String),
 
+    #[error("Browser failed: {0}")]
+    BrowserError(String),
+
     #[error("Rate limited")]
     RateLimited,
+
+    // The engine served a CAPTCHA or consent page instead of results
+    #[error("Blocked: {0}")]
+    Blocked(String),
 }
\ No newline at end of file
@@ -0,0 +1,48 @@
+use maxminddb::{geoip2, MaxMindDBError, Reader};
+use std::net::IpAddr;
+use std::path::Path;
+
+// Region and language guessed from a client IP
+#[derive(Debug, Clone, Default)]
+pub struct GeoDefaults {
+    pub region: Option<String>,
+    pub language: Option<String>,
+}
+
+// Country lookups against a MaxMind (GeoLite2/GeoIP2 Country or City) database
+pub struct GeoIp {
+    reader: Reader<Vec<u8>>,
+}
+
+impl GeoIp {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, MaxMindDBError> {
+        Ok(Self {
+            reader: Reader::open_readfile(path)?,
+        })
+    }
+
+    // Derive a default region/language for the given client IP, if the database knows it
+    pub fn lookup(&self, ip: IpAddr) -> Option<GeoDefaults> {
+        let record: geoip2::Country = self.reader.lookup(ip).ok()?;
+        let iso_code = record.country?.iso_code?.to_lowercase();
+
+        Some(GeoDefaults {
+            language: language_for_country(&iso_code).map(String::from),
+            region: Some(iso_code),
+        })
+    }
+}
+
+// Main language spoken in a country, for the countries we care the most about
+fn language_for_country(iso_code: &str) -> Option<&'static str> {
+    match iso_code {
+        "fr" | "be" | "lu" | "mc" | "ch" => Some("fr"),
+        "ca" | "us" | "gb" | "ie" | "au" | "nz" => Some("en"),
+        "de" | "at" => Some("de"),
+        "es" | "mx" | "ar" | "co" | "cl" => Some("es"),
+        "it" => Some("it"),
+        "pt" | "br" => Some("pt"),
+        "nl" => Some("nl"),
+        _ => None,
+    }
+}
@@ -0,0 +1,52 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+// Outcomes of an engine's most recent searches, oldest first
+#[derive(Default)]
+struct Window {
+    outcomes: VecDeque<bool>,
+    successes: usize,
+}
+
+// Per-engine success rate over a sliding window of the last `size` attempts. Each engine has
+// its own lock, held only to push one outcome, so engines never contend with each other.
+pub struct EngineHealth {
+    windows: HashMap<String, Mutex<Window>>,
+    size: usize,
+}
+
+impl EngineHealth {
+    pub fn new<'a>(engines: impl IntoIterator<Item = &'a str>, size: usize) -> Self {
+        Self {
+            windows: engines
+                .into_iter()
+                .map(|engine| (engine.to_string(), Mutex::new(Window::default())))
+                .collect(),
+            size: size.max(1),
+        }
+    }
+
+    // Record one attempt and return the engine's updated success rate
+    pub fn record(&self, engine: &str, success: bool) -> Option<f64> {
+        let mut window = self.windows.get(engine)?.lock().unwrap();
+        window.outcomes.push_back(success);
+        window.successes += success as usize;
+        if window.outcomes.len() > self.size {
+            let dropped = window.outcomes.pop_front().unwrap_or_default();
+            window.successes -= dropped as usize;
+        }
+
+        Some(window.successes as f64 / window.outcomes.len() as f64)
+    }
+
+    // Success rate and attempt count within the window; no rate before the first attempt
+    pub fn stats(&self, engine: &str) -> (Option<f64>, usize) {
+        let Some(window) = self.windows.get(engine) else {
+            return (None, 0);
+        };
+        let window = window.lock().unwrap();
+        let attempts = window.outcomes.len();
+
+        ((attempts > 0).then(|| window.successes as f64 / attempts as f64), attempts)
+    }
+}
@@ -0,0 +1,55 @@
+use serde::Deserialize;
+
+// Queries containing one of `keywords` (whole words, case-insensitive) have intent `intent`
+// and are only sent to `engines`, e.g.
+// `{"intent": "code", "keywords": ["rust", "api", "docs"], "engines": ["Google", "Wikipedia"]}`
+#[derive(Debug, Clone, Deserialize)]
+pub struct IntentRule {
+    pub intent: String,
+    pub keywords: Vec<String>,
+    pub engines: Vec<String>,
+}
+
+impl IntentRule {
+    pub fn routes_to(&self, engine: &str) -> bool {
+        self.engines.iter().any(|name| name.eq_ignore_ascii_case(engine))
+    }
+
+    fn matches(&self, padded_query: &str) -> bool {
+        self.keywords.iter().any(|keyword| {
+            let keyword = keyword.split_whitespace().collect::<Vec<_>>().join(" ");
+            !keyword.is_empty() && padded_query.contains(&format!(" {} ", keyword.to_lowercase()))
+        })
+    }
+}
+
+// Keyword-based query intent: rules are tried in order and the first match wins. Queries
+// matching no rule have no intent and go to every engine.
+#[derive(Debug, Clone, Default)]
+pub struct IntentClassifier {
+    rules: Vec<IntentRule>,
+}
+
+impl IntentClassifier {
+    pub fn new(rules: Vec<IntentRule>) -> Self {
+        Self { rules }
+    }
+
+    pub fn classify(&self, query: &str) -> Option<&IntentRule> {
+        if self.rules.is_empty() {
+            return None;
+        }
+
+        // `c++` and `c#` keep their symbols, other punctuation around words is dropped
+        let is_noise = |c: char| c.is_ascii_punctuation() && c != '+' && c != '#';
+        let words: Vec<String> = query
+            .split_whitespace()
+            .map(|word| word.trim_matches(is_noise))
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+        let padded_query = format!(" {} ", words.join(" "));
+
+        self.rules.iter().find(|rule| rule.matches(&padded_query))
+    }
+}
@@ -0,0 +1,358 @@
+use crate::error::SearchError;
+use crate::query::{encode_query, DateFilter, OperatorKind};
+use crate::scraper::{
+    base_url_override, resolve_link, FaviconProvider, SearchEngine, SearchPage, SearchResult,
+};
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::Value;
+use std::time::Duration;
+
+// Description of a JSON search API, loaded from config
+#[derive(Debug, Clone, Deserialize)]
+pub struct JsonApiConfig {
+    pub name: String,
+    // Endpoint with `{query}`, `{page}` and `{offset}` placeholders,
+    // e.g. `https://api.example.com/search?q={query}&offset={offset}`
+    pub endpoint: String,
+    // Header carrying the API key, e.g. `X-Subscription-Token`
+    pub api_key_header: Option<String>,
+    pub api_key: Option<String>,
+    // Paths are dot-separated keys/indices, e.g. `web.results` or `$.items.0.title`
+    pub results_path: String,
+    pub title_path: String,
+    pub link_path: String,
+    pub snippet_path: String,
+    #[serde(default = "default_page_size")]
+    pub page_size: u32,
+    // Operators the API understands (`site`, `filetype`, `intitle`, `inurl`); all by default
+    pub supported_operators: Option<Vec<String>>,
+}
+
+fn default_page_size() -> u32 {
+    10
+}
+
+// Search engine backed by an arbitrary JSON API, mapped through `JsonApiConfig`
+pub struct JsonApiEngine {
+    config: JsonApiConfig,
+    supported_operators: Vec<OperatorKind>,
+    // Engines are built once at startup, so leaking the name gives us the `&'static str`
+    // the `SearchEngine` trait expects
+    name: &'static str,
+    base_url: &'static str,
+    client: reqwest::Client,
+}
+
+impl JsonApiEngine {
+    pub fn new(config: JsonApiConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(10))
+            .timeout(Duration::from_secs(30))
+            .build()
+            .unwrap();
+
+        let supported_operators = match &config.supported_operators {
+            Some(names) => OperatorKind::ALL
+                .iter()
+                .copied()
+                .filter(|kind| names.iter().any(|name| name.eq_ignore_ascii_case(kind.name())))
+                .collect(),
+            None => OperatorKind::ALL.to_vec(),
+        };
+
+        Self {
+            supported_operators,
+            name: Box::leak(config.name.clone().into_boxed_str()),
+            base_url: Box::leak(config.endpoint.clone().into_boxed_str()),
+            config,
+            client,
+        }
+    }
+
+    pub fn build_url(&self, query: &str, page: u32) -> String {
+        let page = page.max(1);
+        self.config
+            .endpoint
+            .replace("{query}", &encode_query(query))
+            .replace("{page}", &page.to_string())
+            .replace("{offset}", &((page - 1) * self.config.page_size).to_string())
+    }
+
+    async fn fetch_json(&self, url: &str) -> Result<String, SearchError> {
+        let mut request = self.client.get(url).header("Accept", "application/json");
+
+        if let (Some(header), Some(key)) = (&self.config.api_key_header, &self.config.api_key) {
+            request = request.header(header.as_str(), key.as_str());
+        }
+
+        Ok(request.send().await?.error_for_status()?.text().await?)
+    }
+
+    fn extract_results(&self, json: &Value) -> Vec<SearchResult> {
+        json_path(json, &self.config.results_path)
+            .and_then(|results| results.as_array())
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(|item| {
+                        let title = json_string(item, &self.config.title_path)?;
+                        let link = json_string(item, &self.config.link_path)?;
+                        let link = resolve_link(self.base_url, &link)?;
+
+                        Some(SearchResult {
+                            title,
+                            link,
+                            snippet: json_string(item, &self.config.snippet_path)
+                                .unwrap_or_default(),
+                            source: self.name().to_string(),
+                            sources: Vec::new(),
+                            score: 0.0,
+                            raw_score: None,
+                            flags: Vec::new(),
+                            favicon_url: None,
+                            site_name: None,
+                            breadcrumbs: vec![],
+                            display_url: None,
+                            sitelinks: vec![],
+                            rating: None,
+                            price: None,
+                            rank: 0,
+                            kind: None,
+                            age_text: None,
+                            publish_date: None,
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+// Resolve a dot-separated path (keys or array indices, optional `$.` prefix) in a JSON value
+pub fn json_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.trim_start_matches('$')
+        .split('.')
+        .filter(|segment| !segment.is_empty())
+        .try_fold(value, |current, segment| match current {
+            Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+            _ => current.get(segment),
+        })
+}
+
+fn json_string(value: &Value, path: &str) -> Option<String> {
+    json_path(value, path)
+        .and_then(|v| v.as_str())
+        .map(|s| s.trim().to_string())
+}
+
+#[async_trait]
+impl SearchEngine for JsonApiEngine {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn base_url(&self) -> &'static str {
+        self.base_url
+    }
+
+    fn supported_operators(&self) -> &[OperatorKind] {
+        &self.supported_operators
+    }
+
+    async fn search(
+        &self,
+        query: &str,
+        page: u32,
+        _date_range: Option<&str>,
+        _date_filter: &DateFilter,
+        _region: Option<&str>,
+        _language: Option<&str>,
+    ) -> Result<SearchPage, SearchError> {
+        let body = self.fetch_json(&self.build_url(query, page)).await?;
+        let json = serde_json::from_str::<Value>(&body)
+            .map_err(|e| SearchError::ParsingError(e.to_string()))?;
+
+        Ok(self.extract_results(&json).into())
+    }
+
+    fn parse_results(&self, body: &str) -> Vec<SearchResult> {
+        serde_json::from_str::<Value>(body)
+            .map(|json| self.extract_results(&json))
+            .unwrap_or_default()
+    }
+}
+
+// Official Google Custom Search JSON API, for deployments that prefer paying over scraping
+pub struct GoogleCustomSearchEngine {
+    api_key: String,
+    cse_id: String,
+    base_url: &'static str,
+    client: reqwest::Client,
+    favicon_provider: FaviconProvider,
+}
+
+impl GoogleCustomSearchEngine {
+    pub fn new(api_key: String, cse_id: String) -> Self {
+        let client = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(10))
+            .timeout(Duration::from_secs(30))
+            .build()
+            .unwrap();
+
+        Self {
+            api_key,
+            cse_id,
+            base_url: base_url_override(
+                "GOOGLE_CSE_BASE_URL",
+                "https://www.googleapis.com/customsearch/v1",
+            ),
+            client,
+            favicon_provider: FaviconProvider::default(),
+        }
+    }
+
+    pub fn with_favicon_provider(mut self, favicon_provider: FaviconProvider) -> Self {
+        self.favicon_provider = favicon_provider;
+        self
+    }
+
+    pub fn build_url(
+        &self,
+        query: &str,
+        page: u32,
+        date_range: Option<&str>,
+        date_filter: &DateFilter,
+        region: Option<&str>,
+        language: Option<&str>,
+    ) -> String {
+        // The API pages by 1-based result index, 10 results at a time
+        let start = (page.max(1) - 1) * 10 + 1;
+        let mut url =
+            url::Url::parse(self.base_url()).expect("base_url_override only keeps valid URLs");
+        {
+            let mut params = url.query_pairs_mut();
+            params
+                .append_pair("key", &self.api_key)
+                .append_pair("cx", &self.cse_id)
+                .append_pair("q", query)
+                .append_pair("start", &start.to_string());
+
+            if let Some(range) = date_range.filter(|d| matches!(*d, "d" | "w" | "m" | "y")) {
+                params.append_pair("dateRestrict", &format!("{}1", range));
+            }
+            // Absolute ranges go through date sorting restricted to `start:end`
+            if !date_filter.is_empty() {
+                let start = date_filter.after.map(|date| date.format("%Y%m%d").to_string());
+                let end = date_filter
+                    .before
+                    .unwrap_or_else(|| chrono::Utc::now().date_naive())
+                    .format("%Y%m%d");
+                params.append_pair(
+                    "sort",
+                    &format!("date:r:{}:{}", start.as_deref().unwrap_or("19700101"), end),
+                );
+            }
+            if let Some(region) = region.filter(|r| r.len() == 2) {
+                params.append_pair("gl", region);
+            }
+            if let Some(language) = language.filter(|l| l.len() == 2) {
+                params.append_pair("hl", language);
+            }
+        }
+
+        url.to_string()
+    }
+
+    fn extract_results(&self, json: &Value) -> Vec<SearchResult> {
+        // No `items` means no results for this query
+        json.get("items")
+            .and_then(|items| items.as_array())
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(|item| {
+                        let title = json_string(item, "title")?;
+                        let link = json_string(item, "link")?;
+                        let display_link = json_string(item, "displayLink");
+
+                        let favicon_url = match self.favicon_provider {
+                            FaviconProvider::None => None,
+                            provider => json_string(item, "pagemap.cse_thumbnail.0.src")
+                                .or_else(|| provider.favicon_url(display_link.as_deref()?)),
+                        };
+
+                        Some(SearchResult {
+                            title,
+                            link,
+                            snippet: json_string(item, "snippet").unwrap_or_default(),
+                            source: self.name().to_string(),
+                            sources: Vec::new(),
+                            score: 0.0,
+                            raw_score: None,
+                            flags: Vec::new(),
+                            favicon_url,
+                            site_name: json_string(item, "pagemap.metatags.0.og:site_name"),
+                            breadcrumbs: vec![],
+                            display_url: None,
+                            sitelinks: vec![],
+                            rating: None,
+                            price: None,
+                            rank: 0,
+                            kind: None,
+                            age_text: None,
+                            publish_date: None,
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[async_trait]
+impl SearchEngine for GoogleCustomSearchEngine {
+    fn name(&self) -> &'static str {
+        "GoogleCustomSearch"
+    }
+
+    fn base_url(&self) -> &'static str {
+        self.base_url
+    }
+
+    fn supports_or(&self) -> bool {
+        true
+    }
+
+    async fn search(
+        &self,
+        query: &str,
+        page: u32,
+        date_range: Option<&str>,
+        date_filter: &DateFilter,
+        region: Option<&str>,
+        language: Option<&str>,
+    ) -> Result<SearchPage, SearchError> {
+        let url = self.build_url(query, page, date_range, date_filter, region, language);
+        let body = self
+            .client
+            .get(&url)
+            .header("Accept", "application/json")
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+
+        let json = serde_json::from_str::<Value>(&body)
+            .map_err(|e| SearchError::ParsingError(e.to_string()))?;
+
+        Ok(self.extract_results(&json).into())
+    }
+
+    fn parse_results(&self, body: &str) -> Vec<SearchResult> {
+        serde_json::from_str::<Value>(body)
+            .map(|json| self.extract_results(&json))
+            .unwrap_or_default()
+    }
+}
@@ -1,6 +1,14 @@
+pub mod browser;
 pub mod cache;
+pub mod config;
 pub mod error;
+pub mod geoip;
+pub mod health;
+pub mod intent;
+pub mod json_api;
 pub mod metrics;
+pub mod query;
 pub mod rate_limiter;
 pub mod scraper;
-pub mod scoring;
\ No newline at end of file
+pub mod scoring;
+pub mod telemetry;
\ No newline at end of file
@@ -1,35 +1,82 @@
 use axum::extract::rejection::JsonRejection;
 use axum::extract::FromRequest;
-use axum::extract::{Query, State};
-use axum::http::StatusCode;
+use axum::extract::{ConnectInfo, Query, State};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
 use axum::response::{IntoResponse, Response};
-use axum::{routing::get, Router};
-use futures::stream::{FuturesUnordered, StreamExt};
-use search::cache::{Cache, RedisCache};
+use axum::routing::{get, post};
+use axum::Router;
+use futures::future::join_all;
+use futures::stream::{self, StreamExt};
+use metrics_exporter_prometheus::PrometheusHandle;
+use rand::Rng;
+use search::browser::HeadlessBrowser;
+use search::cache::{cache_key, Cache, CacheExt, InMemoryCache, RedisCache};
+use search::config::{CacheControlPolicy, Config, DefaultSearchParams};
+use search::geoip::GeoIp;
+use search::health::EngineHealth;
+use search::intent::IntentRule;
+use search::json_api::{GoogleCustomSearchEngine, JsonApiEngine};
 use search::metrics::SearchMetrics;
-use search::rate_limiter::RateLimiter;
-use search::scoring::ResultScorer;
-use search::scraper::SearchResult;
-use search::scraper::{DuckDuckGoScraper, GoogleScraper, SearchEngine};
+use search::query::{encode_query, normalize_query, parse_query, rank_suggestions, ParsedQuery};
+use search::rate_limiter::{ClientRateLimiter, RateLimiter};
+use search::scoring::{
+    DedupScope, DuplicateTitle, MergeMode, ResultScorer, ScoreBreakdown, ScoringConfig,
+    TitleCopies,
+};
+use search::scraper::{FeaturedSnippet, SearchPage, SearchResult};
+use search::scraper::{BraveScraper, DuckDuckGoScraper, GoogleScraper, SearchEngine};
+use search::scraper::StartpageScraper;
 use search::scraper::QuickAnswer;
+use search::telemetry::{self, MetricsBackend};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::BinaryHeap;
-use std::net::SocketAddr;
+use std::collections::{BinaryHeap, HashMap};
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::Semaphore;
 use tower_http::cors::CorsLayer;
+use tracing::{Instrument, Span};
+use xxhash_rust::xxh3::xxh3_64;
 use tower_http::services::ServeDir;
 
+const CLIENT_ID_HEADER: &str = "x-client-id";
+// Client deadline for the whole search, in milliseconds from receipt
+const REQUEST_TIMEOUT_HEADER: &str = "x-request-timeout-ms";
+
+tokio::task_local! {
+    // Deadline of the search running in this task, from `X-Request-Timeout-Ms`. Engines still
+    // running when it passes are dropped and the search goes on with what came back.
+    static SEARCH_DEADLINE: Option<tokio::time::Instant>;
+}
+
 struct SearchService {
     engines: Vec<Box<dyn SearchEngine>>,
-    cache: Arc<RedisCache>,
+    cache: Arc<dyn Cache>,
     rate_limiter: Arc<RateLimiter>,
+    // Recent success rate of each engine
+    health: EngineHealth,
+    config: Arc<Config>,
+    // Built once from `config.scoring`
+    scorer: ResultScorer,
+    // Autocomplete fires on every keystroke, so it gets its own short-timeout client
+    autocomplete_client: reqwest::Client,
+    // Short-timeout client for `verify_favicons`
+    favicon_client: reqwest::Client,
+    // Shared by every outgoing scrape request, engine fetches and favicon checks alike, so
+    // concurrent searches can't multiply the load on the network
+    scrape_permits: Semaphore,
 }
 
 #[derive(Clone)]
 struct AppState {
     search_service: Arc<SearchService>,
+    config: Arc<Config>,
+    geoip: Option<Arc<GeoIp>>,
+    // Searches per client IP; `None` when unlimited
+    client_limiter: Option<Arc<ClientRateLimiter>>,
+    // Renders `/metrics` with the Prometheus backend
+    prometheus: Option<PrometheusHandle>,
 }
 
 #[derive(Deserialize)]
@@ -45,23 +92,254 @@ struct SearchParams {
     date_range: Option<String>,
     region: Option<String>,
     language: Option<String>,
+    // `false` keeps each engine's native order, merged round-robin, with no scoring
+    scoring: Option<bool>,
+    // HEAD-check favicon URLs and drop the broken ones; slower, so off unless asked for
+    #[serde(default)]
+    verify_favicons: bool,
+    #[serde(default)]
+    format: ResponseFormat,
+    // Adds per-engine latencies (`engine_timings`) to the response
+    #[serde(default)]
+    debug: bool,
+    // Keeps each result's scoring `flags` in the response
+    #[serde(default)]
+    explain: bool,
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum ResponseFormat {
+    #[default]
+    Json,
+    // Results only, as `title,link,snippet,source,score` rows for spreadsheets
+    Csv,
+}
+
+impl SearchParams {
+    // Fill what the request left out from the server-wide defaults. Region and language are
+    // left alone, since the client's GeoIP guess comes before the defaults for those.
+    fn with_defaults(self, defaults: &DefaultSearchParams) -> Self {
+        Self {
+            date_range: self.date_range.or_else(|| defaults.date_range.clone()),
+            scoring: self.scoring.or(defaults.scoring),
+            ..self
+        }
+    }
+}
+
+// What a search produced, before it's wrapped in the response envelope.
+// This is also what gets cached.
+#[derive(Serialize, Deserialize)]
+struct SearchOutcome {
+    results: Vec<SearchResult>,
+    people_also_ask: Vec<String>,
+    #[serde(default)]
+    featured_snippet: Option<FeaturedSnippet>,
+    #[serde(default)]
+    intent: Option<String>,
+    #[serde(default)]
+    exhausted_engines: Vec<String>,
+    #[serde(default)]
+    empty_pages: Vec<EmptyPage>,
+    // Unix time at which the cached copy expires; 0 when the outcome wasn't cached
+    #[serde(default)]
+    expires_at: u64,
+    #[serde(skip)]
+    cached: bool,
+    #[serde(skip)]
+    stale: bool,
+    // Milliseconds each engine took to answer, for engines that did; empty for cached outcomes
+    #[serde(skip)]
+    engine_timings: HashMap<String, u64>,
+}
+
+// Why an engine's page came back empty, told apart by retrying it once
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum EmptyPageCause {
+    // The retry was empty too
+    NoResults,
+    // The retry found results, or failed outright: the engine was (or still is) blocking us
+    Blocked,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EmptyPage {
+    engine: String,
+    cause: EmptyPageCause,
+    // The retry's results replaced the empty page
+    recovered: bool,
+}
+
+// Response envelope for the search API: echoes the request and reports timing
+#[derive(Serialize)]
+struct SearchResponse {
+    query: String,
+    page: u32,
+    took_ms: u64,
+    cached: bool,
+    // Served from an expired copy because every engine failed
+    stale: bool,
+    results: Vec<SearchResult>,
+    people_also_ask: Vec<String>,
+    // Answer box to show above the results
+    featured_snippet: Option<FeaturedSnippet>,
+    // Intent the query was classified as, which decided the engines queried
+    intent: Option<String>,
+    // Engines that answered this page with their first page again, i.e. have no more pages.
+    // Their repeated results are left out.
+    exhausted_engines: Vec<String>,
+    // Engines whose page came back empty after their previous page had results, and why
+    empty_pages: Vec<EmptyPage>,
+    // Engine name -> milliseconds, only with `debug=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    engine_timings: Option<HashMap<String, u64>>,
+}
+
+// `/api/admin/score-preview` body: `config` fields left out take their defaults
+#[derive(Deserialize)]
+struct ScorePreviewRequest {
+    query: String,
+    config: ScoringConfig,
+}
+
+#[derive(Serialize)]
+struct ScoredResult {
+    #[serde(flatten)]
+    result: SearchResult,
+    breakdown: ScoreBreakdown,
+}
+
+#[derive(Serialize)]
+struct ScorePreviewResponse {
+    query: String,
+    results: Vec<ScoredResult>,
+}
+
+// `/api/admin/cache/keys?pattern=search:*&cursor=0`
+#[derive(Deserialize)]
+struct CacheKeysParams {
+    #[serde(default = "match_all")]
+    pattern: String,
+    #[serde(default)]
+    cursor: u64,
+}
+
+fn match_all() -> String {
+    "*".to_string()
+}
+
+#[derive(Serialize)]
+struct CacheKeysResponse {
+    keys: Vec<String>,
+    // Pass back as `cursor` for the next page; 0 when the scan is complete
+    cursor: u64,
+}
+
+// One engine's column in `/api/compare`
+#[derive(Serialize)]
+struct EngineComparison {
+    engine: String,
+    // `false` when the engine failed or was rate limited
+    responded: bool,
+    // In the engine's own order (`rank`), scored but not deduplicated
+    results: Vec<SearchResult>,
+}
+
+#[derive(Serialize)]
+struct CompareResponse {
+    query: String,
+    took_ms: u64,
+    engines: Vec<EngineComparison>,
+}
+
+// One engine in `/api/engines`
+#[derive(Serialize)]
+struct EngineStatus {
+    name: String,
+    // Over the last `attempts` searches; `null` before the first one
+    success_rate: Option<f64>,
+    attempts: usize,
+}
+
+#[derive(Serialize)]
+struct EnginesResponse {
+    engines: Vec<EngineStatus>,
 }
 
 // Nouveau paramètre pour les quick answers
 #[derive(Deserialize)]
 struct QuickAnswerParams {
     query: String,
+    // Every engine's answer instead of only the most confident one
+    #[serde(default)]
+    all: bool,
 }
 
 impl SearchService {
-    pub fn new(cache: RedisCache) -> Self {
+    pub fn new(cache: Arc<dyn Cache>, config: Arc<Config>) -> Self {
+        let referer = |engine: &str| config.engine_referers.get(engine).map(String::as_str);
+        let mut google = GoogleScraper::new()
+            .with_favicon_provider(config.favicon_provider)
+            .with_referer(referer("Google"))
+            .with_drop_ads(config.drop_ads);
+        if let Some(webdriver_url) = &config.headless_browser_url {
+            google = google.with_headless_browser(Arc::new(HeadlessBrowser::new(webdriver_url)));
+        }
+
+        let mut engines: Vec<Box<dyn SearchEngine>> = vec![
+            Box::new(google),
+            Box::new(
+                DuckDuckGoScraper::new()
+                    .with_favicon_provider(config.favicon_provider)
+                    .with_referer(referer("DuckDuckGo")),
+            ),
+            Box::new(
+                BraveScraper::new()
+                    .with_favicon_provider(config.favicon_provider)
+                    .with_referer(referer("Brave")),
+            ),
+            Box::new(
+                StartpageScraper::new()
+                    .with_favicon_provider(config.favicon_provider)
+                    .with_referer(referer("Startpage")),
+            ),
+        ];
+
+        // JSON API engines declared in config join the scrapers in the fan-out
+        for api in &config.json_api_engines {
+            engines.push(Box::new(JsonApiEngine::new(api.clone())));
+        }
+
+        if let (Some(api_key), Some(cse_id)) = (&config.google_api_key, &config.google_cse_id) {
+            engines.push(Box::new(
+                GoogleCustomSearchEngine::new(api_key.clone(), cse_id.clone())
+                    .with_favicon_provider(config.favicon_provider),
+            ));
+        }
+
+        let health = EngineHealth::new(
+            engines.iter().map(|engine| engine.name()),
+            config.engine_health_window,
+        );
+
         Self {
-            engines: vec![
-                Box::new(GoogleScraper::new()),
-                Box::new(DuckDuckGoScraper::new()),
-            ],
-            cache: Arc::new(cache),
-            rate_limiter: Arc::new(RateLimiter::new()),
+            engines,
+            cache,
+            rate_limiter: Arc::new(RateLimiter::new().with_schedule(config.rate_schedule.clone())),
+            health,
+            autocomplete_client: reqwest::Client::builder()
+                .timeout(Duration::from_millis(config.autocomplete_timeout_ms))
+                .build()
+                .unwrap(),
+            favicon_client: reqwest::Client::builder()
+                .timeout(Duration::from_millis(config.favicon_check_timeout_ms))
+                .build()
+                .unwrap(),
+            scrape_permits: Semaphore::new(config.max_concurrent_scrapes.max(1)),
+            scorer: ResultScorer::new(config.scoring.clone()),
+            config,
         }
     }
 
@@ -73,72 +351,662 @@ impl SearchService {
         date_range: Option<&str>,
         region: Option<&str>,
         language: Option<&str>,
-    ) -> Vec<SearchResult> {
-        // Cache key includes new parameters to avoid returning incorrect results
-        let cache_key = format!(
-            "search:{}:{}:{:?}:{:?}:{:?}",
-            query,
-            page.unwrap_or(1),
-            date_range,
-            region,
-            language
-        );
+        scoring: bool,
+    ) -> SearchOutcome {
+        // Normalize first so the cache key and every engine see the same query
+        let normalized_query;
+        let query = if self.config.normalize_query {
+            normalized_query = normalize_query(query);
+            normalized_query.as_str()
+        } else {
+            query
+        };
+
+        let cache_key =
+            Self::search_cache_key(query, page.unwrap_or(1), date_range, region, language, scoring);
 
         // Check cache first
-        if let Some(cached_results) = self.cache.get(&cache_key).await {
+        if let Some(cached_outcome) = self.cache.get::<SearchOutcome>(&cache_key).await {
             SearchMetrics::record_cache_hit();
-            return cached_results;
+            return SearchOutcome {
+                cached: true,
+                stale: false,
+                ..cached_outcome
+            };
         }
 
         SearchMetrics::record_cache_miss();
 
-        let mut futures = FuturesUnordered::new();
-        for engine in &self.engines {
-            let query = query.to_string();
-            let page = page.clone();
-            let date_range = date_range.map(|s| s.to_string());
-            let region = region.map(|s| s.to_string());
-            let language = language.map(|s| s.to_string());
-            let rate_limiter = &self.rate_limiter;
-
-            futures.push(async move {
-                // Check rate limit
-                if !rate_limiter.check_rate_limit(engine.name()).await {
-                    return Vec::new();
+        // Operators are validated once, then each engine gets only those it supports
+        let parsed_query = parse_query(query);
+        // Rules naming none of our engines are ignored rather than leaving nothing to query
+        let intent = self
+            .config
+            .intent_classifier
+            .classify(&parsed_query.terms)
+            .filter(|rule| self.engines.iter().any(|engine| rule.routes_to(engine.name())));
+        let engines_queried = self
+            .engines
+            .iter()
+            .filter(|engine| intent.is_none_or(|rule| rule.routes_to(engine.name())))
+            .count();
+        let (mut pages, mut engine_timings) = self
+            .query_engines(&parsed_query, intent, page, date_range, region, language)
+            .await;
+        // Retrying empty pages needs the engine's previous page, as cached
+        let previous_page = match page {
+            Some(page) if page > 1 && self.config.retry_empty_pages => {
+                let previous_key =
+                    Self::search_cache_key(query, page - 1, date_range, region, language, scoring);
+                self.cache.get::<SearchOutcome>(&previous_key).await
+            }
+            _ => None,
+        };
+        let mut empty_pages = Vec::new();
+        // Retries are bounded by the client's deadline too, and skipped once it has passed
+        let deadline = SEARCH_DEADLINE.try_with(|deadline| *deadline).ok().flatten();
+        for index in previous_page
+            .map(|previous| self.emptied_engines(&pages, &previous))
+            .unwrap_or_default()
+        {
+            let engine = self.engines[index].as_ref();
+            let started = Instant::now();
+            let retry =
+                self.query_engine(engine, &parsed_query, page, date_range, region, language);
+            let retried = match deadline {
+                Some(deadline) if deadline <= tokio::time::Instant::now() => None,
+                Some(deadline) => tokio::time::timeout_at(deadline, retry).await.unwrap_or(None),
+                None => retry.await,
+            };
+            // The retry is part of what the engine cost this search
+            *engine_timings.entry(engine.name().to_string()).or_default() +=
+                started.elapsed().as_millis() as u64;
+            let (cause, recovered) = match retried {
+                Some(retried) if !retried.results.is_empty() => {
+                    pages[index] = Some(retried);
+                    (EmptyPageCause::Blocked, true)
                 }
+                Some(_) => (EmptyPageCause::NoResults, false),
+                None => (EmptyPageCause::Blocked, false),
+            };
+            empty_pages.push(EmptyPage {
+                engine: engine.name().to_string(),
+                cause,
+                recovered,
+            });
+        }
+
+        let mut engine_results = vec![Vec::new(); self.engines.len()];
+        let mut people_also_ask: Vec<String> = Vec::new();
+        let mut featured_snippet = None;
+        let mut exhausted_engines = Vec::new();
+        let mut engines_responded = 0;
+        let first_page_key = format!("{}:{:?}:{:?}:{:?}", query, date_range, region, language);
+        for (index, search_page) in pages.into_iter().enumerate() {
+            // `None` means the engine failed or was rate limited
+            let Some(search_page) = search_page else {
+                continue;
+            };
 
-                // Perform search with additional parameters if supported
-                match engine
-                    .search(
-                        &query,
+            engines_responded += 1;
+            let engine = self.engines[index].name();
+            let repeated = self.config.detect_repeated_pages
+                && self
+                    .repeats_first_page(
+                        engine,
+                        &first_page_key,
                         page.unwrap_or(1),
-                        date_range.as_deref(),
-                        region.as_deref(),
-                        language.as_deref(),
+                        &search_page.results,
                     )
-                    .await
-                {
-                    Ok(results) => {
-                        SearchMetrics::record_search_result(engine.name(), true);
-                        results
-                    }
-                    Err(_) => {
-                        SearchMetrics::record_search_result(engine.name(), false);
-                        Vec::new()
-                    }
+                    .await;
+            if repeated {
+                exhausted_engines.push(engine.to_string());
+            } else {
+                engine_results[index] = search_page.results;
+            }
+            // Only one answer box is shown, the first engine's
+            featured_snippet = featured_snippet.or(search_page.featured_snippet);
+            for question in search_page.people_also_ask {
+                if !people_also_ask.contains(&question) {
+                    people_also_ask.push(question);
                 }
-            });
+            }
+        }
+
+        // Engines failed and the rest found nothing: fall back on the last good copy. When every
+        // engine is down any copy still retained will do, otherwise only one whose regular
+        // cache entry expired less than `stale_grace_secs` ago
+        let fetch_failed = engines_responded < engines_queried
+            && engine_results.iter().all(|results| results.is_empty());
+        let total_failure = engines_queried > 0 && engines_responded == 0;
+        if fetch_failed && (self.config.serve_stale_on_failure || self.config.stale_grace_secs > 0)
+        {
+            let stale_outcome = self.cache.get::<SearchOutcome>(&stale_key(&cache_key)).await;
+            let usable = |outcome: &SearchOutcome| {
+                (total_failure && self.config.serve_stale_on_failure)
+                    || outcome.expires_at + self.config.stale_grace_secs >= unix_now()
+            };
+            if let Some(stale_outcome) = stale_outcome.filter(usable) {
+                return SearchOutcome {
+                    stale: true,
+                    ..stale_outcome
+                };
+            }
+        }
+
+        // Point AMP cache links at the real page, which also lets them dedup against it, and
+        // known-HTTPS sites at their secure URL. Until the final ranking, `rank` holds the
+        // result's position within its engine, which scoring may reward.
+        let engine_positions = engine_results.iter_mut().flat_map(|results| {
+            results.iter_mut().enumerate().map(|(index, result)| (index + 1, result))
+        });
+        for (position, result) in engine_positions {
+            result.rank = position;
+            if let Some(canonical) = ResultScorer::de_amp(&result.link) {
+                result.link = canonical;
+            }
+            if let Some(secure) =
+                ResultScorer::upgrade_to_https(&result.link, &self.config.https_upgrade_domains)
+            {
+                result.link = secure;
+            }
+            result.kind = ResultScorer::classify_kind(&result.link, &self.config.result_kind_rules);
+        }
+
+        let min_title_length = self.config.min_title_length;
+        for results in &mut engine_results {
+            results.retain(|result| ResultScorer::has_min_title(result, min_title_length));
+        }
+
+        if let Some(min_length) = self.config.min_snippet_length {
+            for results in &mut engine_results {
+                results.retain(|result| ResultScorer::has_min_snippet(result, min_length));
+            }
+        }
+
+        if let Some(max_candidates) = self.config.max_candidates {
+            ResultScorer::cap_candidates(&mut engine_results, max_candidates);
+        }
+
+        let final_results = if !scoring {
+            // Raw passthrough: engines keep their native order, merged round-robin
+            ResultScorer::interleave(engine_results)
+        } else if self.config.merge_mode == MergeMode::Interleave {
+            for results in &mut engine_results {
+                self.score_results(results, query, &parsed_query.terms);
+            }
+            // Copies are found across engines, then each engine's list is rebuilt as it was
+            let lengths: Vec<usize> = engine_results.iter().map(Vec::len).collect();
+            let mut all_results = engine_results.concat();
+            self.penalize_title_copies(&mut all_results);
+            let mut all_results = all_results.into_iter();
+            let engine_results = lengths
+                .into_iter()
+                .map(|length| all_results.by_ref().take(length).collect())
+                .collect();
+            ResultScorer::interleave_by_rank(engine_results)
+        } else {
+            self.score_and_sort(engine_results.concat(), query, &parsed_query.terms)
+        };
+
+        // Remove duplicates
+        let mut final_results = ResultScorer::remove_duplicates(
+            final_results,
+            self.config.dedup_scope,
+            self.config.fragment_sensitive_dedup,
+            self.config.title_copies,
+            self.config.dedup_preference.as_ref(),
+            &self.config.duplicate_title,
+            if parsed_query.terms.is_empty() { query } else { &parsed_query.terms },
+        );
+        if let Some(floor) = self.config.engine_result_floor {
+            final_results = ResultScorer::apply_engine_floor(
+                final_results,
+                floor,
+                self.config.engine_floor_window,
+            );
+        }
+        for (index, result) in final_results.iter_mut().enumerate() {
+            result.rank = index + 1;
+        }
+        let kept = fit_response_size(&final_results, self.config.max_response_bytes);
+        if kept < final_results.len() {
+            eprintln!(
+                "Truncated {} results to {} to stay under {} bytes",
+                final_results.len(),
+                kept,
+                self.config.max_response_bytes
+            );
+            final_results.truncate(kept);
+        }
+
+        let mut outcome = SearchOutcome {
+            results: final_results,
+            people_also_ask,
+            featured_snippet,
+            intent: intent.map(|rule| rule.intent.clone()),
+            exhausted_engines,
+            empty_pages,
+            expires_at: 0,
+            cached: false,
+            stale: false,
+            engine_timings,
+        };
+
+        // Degraded results (too few engines answered, a block that didn't clear, or engines
+        // cut off by the client's deadline) aren't cached, so the next request retries
+        let still_blocked = outcome
+            .empty_pages
+            .iter()
+            .any(|empty| empty.cause == EmptyPageCause::Blocked && !empty.recovered);
+        let deadline_passed =
+            deadline.is_some_and(|deadline| deadline.into_std() <= Instant::now());
+        if engines_responded < self.config.min_engines_to_cache.min(engines_queried)
+            || still_blocked
+            || deadline_passed
+        {
+            return outcome;
         }
 
-        let mut all_results = Vec::new();
-        while let Some(results) = futures.next().await {
-            all_results.extend(results);
+        // Cache results, for as long as the query's kind stays fresh
+        let ttl_secs = self
+            .config
+            .cache_ttl_rules
+            .ttl_for(query)
+            .unwrap_or(self.config.cache_ttl_secs);
+        outcome.expires_at = unix_now() + ttl_secs;
+        let cached = self
+            .cache
+            .set(&cache_key, &outcome, Duration::from_secs(ttl_secs))
+            .await;
+        if cached.is_err() {
+            outcome.expires_at = 0;
+        }
+
+        // Keep a longer-lived copy to fall back on when engines are down, for as long as either
+        // fallback may use it. Empty outcomes aren't worth falling back on, and would overwrite
+        // a useful copy.
+        let stale_secs = if self.config.serve_stale_on_failure {
+            self.config.stale_retention_secs.max(self.config.stale_grace_secs)
+        } else {
+            self.config.stale_grace_secs
+        };
+        if stale_secs > 0 && !outcome.results.is_empty() {
+            let _ = self
+                .cache
+                .set(
+                    &stale_key(&cache_key),
+                    &outcome,
+                    Duration::from_secs(ttl_secs + stale_secs),
+                )
+                .await;
+        }
+
+        outcome
+    }
+
+    // Query every engine with its share of the parsed query. Pages come back in engine order,
+    // `None` for engines that failed or were rate limited.
+    // Engines left out by `intent` don't run and get `None`
+    async fn query_engines(
+        &self,
+        parsed_query: &ParsedQuery,
+        intent: Option<&IntentRule>,
+        page: Option<u32>,
+        date_range: Option<&str>,
+        region: Option<&str>,
+        language: Option<&str>,
+    ) -> (Vec<Option<SearchPage>>, HashMap<String, u64>) {
+        // Engines run concurrently, at most `max_concurrent_engines` at a time (all by default)
+        let concurrency = self
+            .config
+            .max_concurrent_engines
+            .unwrap_or(self.engines.len())
+            .max(1);
+        let engine_futures: Vec<_> = self
+            .engines
+            .iter()
+            .enumerate()
+            .filter(|(_, engine)| intent.is_none_or(|rule| rule.routes_to(engine.name())))
+            .map(|(index, engine)| async move {
+                let started = Instant::now();
+                let search_page = self
+                    .query_engine(engine.as_ref(), parsed_query, page, date_range, region, language)
+                    .await;
+
+                // Tagged with the engine's position so results can be merged in a stable order
+                (index, search_page, started.elapsed())
+            })
+            .collect();
+        let mut futures = stream::iter(engine_futures).buffer_unordered(concurrency);
+
+        let deadline = SEARCH_DEADLINE.try_with(|deadline| *deadline).ok().flatten();
+        let mut pages: Vec<Option<SearchPage>> = vec![None; self.engines.len()];
+        // Engines cut off by the deadline have no timing
+        let mut timings = HashMap::new();
+        loop {
+            let next = match deadline {
+                Some(deadline) => match tokio::time::timeout_at(deadline, futures.next()).await {
+                    Ok(next) => next,
+                    Err(_) => break,
+                },
+                None => futures.next().await,
+            };
+            let Some((index, search_page, elapsed)) = next else {
+                break;
+            };
+            pages[index] = search_page;
+            timings.insert(self.engines[index].name().to_string(), elapsed.as_millis() as u64);
+        }
+
+        (pages, timings)
+    }
+
+    // Engines that returned nothing right after a page where they had results (`previous`).
+    // That's often a block rather than the end of their results, so each gets one more try,
+    // rate limits permitting, and its page is replaced when the retry finds something.
+    fn emptied_engines(
+        &self,
+        pages: &[Option<SearchPage>],
+        previous: &SearchOutcome,
+    ) -> Vec<usize> {
+        self.engines
+            .iter()
+            .zip(pages)
+            .enumerate()
+            .filter(|(_, (engine, search_page))| {
+                search_page.as_ref().is_some_and(|p| p.results.is_empty())
+                    && previous.results.iter().any(|result| {
+                        // Hits merged into another engine's copy are only in `sources`
+                        result.source == engine.name()
+                            || result.sources.iter().any(|source| source == engine.name())
+                    })
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    // One engine's page, `None` when it failed or was rate limited
+    async fn query_engine(
+        &self,
+        engine: &dyn SearchEngine,
+        parsed_query: &ParsedQuery,
+        page: Option<u32>,
+        date_range: Option<&str>,
+        region: Option<&str>,
+        language: Option<&str>,
+    ) -> Option<SearchPage> {
+        // Synonyms only go to engines that understand `OR`
+        let query = if engine.supports_or() && !self.config.synonyms.is_empty() {
+            ParsedQuery {
+                terms: self.config.synonyms.expand(&parsed_query.terms),
+                ..parsed_query.clone()
+            }
+            .to_query_string(engine.supported_operators())
+        } else {
+            parsed_query.to_query_string(engine.supported_operators())
+        };
+        let retry_budget = self
+            .config
+            .retry_budgets
+            .get(engine.name())
+            .copied()
+            .unwrap_or(0);
+
+        // Check rate limit
+        if !self.rate_limiter.check_rate_limit(engine.name()).await {
+            return None;
         }
 
-        // Score and sort results
-        for result in &mut all_results {
-            result.score = ResultScorer::score_result(result, query);
+        // Perform search with additional parameters if supported, retrying under
+        // a new identity while the engine's budget lasts
+        let mut attempt = 0;
+        loop {
+            if let Some(&(min, max)) = self.config.politeness_delays.get(engine.name()) {
+                let delay = rand::thread_rng().gen_range(min..=max);
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+            }
+
+            // The semaphore is never closed
+            let permit = self.scrape_permits.acquire().await.unwrap();
+            let started = Instant::now();
+            let result = engine
+                .search(
+                    &query,
+                    page.unwrap_or(1),
+                    date_range,
+                    &parsed_query.date_filter,
+                    region,
+                    language,
+                )
+                .await;
+            SearchMetrics::record_search_time(engine.name(), started.elapsed());
+            drop(permit);
+            if let Ok(search_page) = &result {
+                let count = search_page.results.len() as u64;
+                SearchMetrics::record_results_count(engine.name(), count);
+            }
+
+            let success = match &result {
+                Err(_) if attempt < retry_budget => {
+                    attempt += 1;
+                    engine.rotate_identity();
+                    continue;
+                }
+                result => result.is_ok(),
+            };
+
+            SearchMetrics::record_search_result(engine.name(), success);
+            if let Some(rate) = self.health.record(engine.name(), success) {
+                SearchMetrics::record_engine_success_rate(engine.name(), rate);
+            }
+            return result.ok();
+        }
+    }
+
+    // Drop favicon URLs that don't resolve, checked with HEAD requests. Only an error status
+    // counts as broken: a timeout or network error keeps the favicon.
+    async fn verify_favicons(&self, results: &mut [SearchResult]) {
+        let mut urls: Vec<String> =
+            results.iter().filter_map(|result| result.favicon_url.clone()).collect();
+        urls.sort();
+        urls.dedup();
+
+        let checks = urls.into_iter().map(|url| async move {
+            let _permit = self.scrape_permits.acquire().await.unwrap();
+            let broken = self
+                .favicon_client
+                .head(&url)
+                .send()
+                .await
+                .is_ok_and(|response| !response.status().is_success());
+            (url, broken)
+        });
+        let broken: Vec<String> = stream::iter(checks)
+            .buffer_unordered(self.config.favicon_check_concurrency.max(1))
+            .filter_map(|(url, broken)| async move { broken.then_some(url) })
+            .collect()
+            .await;
+
+        for result in results {
+            if result.favicon_url.as_ref().is_some_and(|url| broken.contains(url)) {
+                result.favicon_url = None;
+            }
         }
+    }
+
+    // Configured engines with their recent reliability
+    fn engine_statuses(&self) -> Vec<EngineStatus> {
+        self.engines
+            .iter()
+            .map(|engine| {
+                let (success_rate, attempts) = self.health.stats(engine.name());
+                EngineStatus {
+                    name: engine.name().to_string(),
+                    success_rate,
+                    attempts,
+                }
+            })
+            .collect()
+    }
+
+    // Every engine's results side by side, each scored but neither merged nor deduplicated,
+    // to see which engine contributes what and whether scoring agrees across engines
+    async fn compare(
+        &self,
+        query: &str,
+        page: Option<u32>,
+        date_range: Option<&str>,
+        region: Option<&str>,
+        language: Option<&str>,
+    ) -> Vec<EngineComparison> {
+        let query = if self.config.normalize_query {
+            normalize_query(query)
+        } else {
+            query.to_string()
+        };
+        let parsed_query = parse_query(&query);
+
+        let pages = self
+            .query_engines(&parsed_query, None, page, date_range, region, language)
+            .await
+            .0;
+
+        self.engines
+            .iter()
+            .zip(pages)
+            .map(|(engine, search_page)| {
+                let responded = search_page.is_some();
+                let mut results = search_page.map(|page| page.results).unwrap_or_default();
+                for (index, result) in results.iter_mut().enumerate() {
+                    result.rank = index + 1;
+                }
+                self.score_results(&mut results, &query, &parsed_query.terms);
+
+                EngineComparison {
+                    engine: engine.name().to_string(),
+                    responded,
+                    results,
+                }
+            })
+            .collect()
+    }
+
+    // Some engines (DuckDuckGo at high offsets) answer any page past their last with their
+    // first page again. Each engine's first-page URL set is remembered for as long as results
+    // are cached, so later pages can be checked against it.
+    async fn repeats_first_page(
+        &self,
+        engine: &str,
+        query_key: &str,
+        page: u32,
+        results: &[SearchResult],
+    ) -> bool {
+        if results.is_empty() {
+            return false;
+        }
+
+        let key = cache_key("first-page", &format!("{}:{}", engine, query_key));
+        let mut links: Vec<&str> = results.iter().map(|result| result.link.as_str()).collect();
+        links.sort_unstable();
+        let fingerprint = xxh3_64(links.join("\n").as_bytes());
+
+        if page <= 1 {
+            let ttl = Duration::from_secs(self.config.cache_ttl_secs);
+            let _ = self.cache.set(&key, &fingerprint, ttl).await;
+            return false;
+        }
+
+        self.cache.get::<u64>(&key).await == Some(fingerprint)
+    }
+
+    // Cache key includes every parameter that changes the results, to avoid returning
+    // incorrect ones
+    fn search_cache_key(
+        query: &str,
+        page: u32,
+        date_range: Option<&str>,
+        region: Option<&str>,
+        language: Option<&str>,
+        scoring: bool,
+    ) -> String {
+        cache_key(
+            "search",
+            &format!(
+                "{}:{}:{:?}:{:?}:{:?}:{}",
+                query, page, date_range, region, language, scoring
+            ),
+        )
+    }
+
+    // Seed the cache with fixed first-page results from a JSON file mapping each query to its
+    // results, so demos and offline setups get deterministic answers without live engines.
+    // Entries are keyed like a request that only has the server-wide defaults.
+    pub async fn preload_cache(&self, path: &str) {
+        let Some(entries) = load_preload_file(path) else {
+            return;
+        };
+
+        let ttl = self.config.preload_cache_ttl_secs;
+        let mut loaded = 0;
+        for (query, results) in entries {
+            let query = if self.config.normalize_query {
+                normalize_query(&query)
+            } else {
+                query
+            };
+
+            // Only results a client could actually display
+            let mut results: Vec<SearchResult> = results
+                .into_iter()
+                .filter(|result| !result.title.is_empty() && result.link.starts_with("http"))
+                .collect();
+            if query.is_empty() || results.is_empty() {
+                eprintln!("Skipping preloaded entry {:?}: no query or no valid results", query);
+                continue;
+            }
+            for (index, result) in results.iter_mut().enumerate() {
+                result.rank = index + 1;
+            }
+
+            let defaults = &self.config.search_defaults;
+            let key = Self::search_cache_key(
+                &query,
+                1,
+                defaults.date_range.as_deref(),
+                defaults.region.as_deref(),
+                defaults.language.as_deref(),
+                defaults.scoring.unwrap_or(true),
+            );
+            let outcome = SearchOutcome {
+                results,
+                people_also_ask: Vec::new(),
+                featured_snippet: None,
+                intent: None,
+                exhausted_engines: Vec::new(),
+                empty_pages: Vec::new(),
+                expires_at: unix_now() + ttl,
+                cached: false,
+                stale: false,
+                engine_timings: HashMap::new(),
+            };
+            if self.cache.set(&key, &outcome, Duration::from_secs(ttl)).await.is_ok() {
+                loaded += 1;
+            }
+        }
+
+        println!("Preloaded {} cache entries from {}", loaded, path);
+    }
+
+    // Score every result against the query and sort them best-first
+    fn score_and_sort(
+        &self,
+        mut all_results: Vec<SearchResult>,
+        query: &str,
+        terms: &str,
+    ) -> Vec<SearchResult> {
+        self.score_results(&mut all_results, query, terms);
+        self.penalize_title_copies(&mut all_results);
 
         // Use a BinaryHeap to sort results by score
         let mut heap = BinaryHeap::new();
@@ -146,36 +1014,199 @@ impl SearchService {
             heap.push(result);
         }
 
-        let mut final_results: Vec<_> = heap.into_sorted_vec();
+        heap.into_sorted_vec()
+    }
 
-        // Remove duplicates
-        final_results = ResultScorer::remove_duplicates(final_results);
+    // With `TitleCopies::Penalize`, lower the score of same-title copies on other domains
+    fn penalize_title_copies(&self, results: &mut [SearchResult]) {
+        if self.config.title_copies == TitleCopies::Penalize {
+            ResultScorer::penalize_title_copies(
+                results,
+                self.config.title_copy_penalty,
+                &self.scorer.config().relevant_urls,
+            );
+        }
+    }
+
+    // Score results in place, keeping their order
+    fn score_results(&self, results: &mut [SearchResult], query: &str, terms: &str) {
+        // Operators say where to look, not what to look for, so they don't count for relevance
+        let scoring_query = if terms.is_empty() { query } else { terms };
+        let weights = self.scorer.config().term_weights(results, scoring_query);
+        for result in results {
+            let breakdown = self.scorer.score_breakdown(result, scoring_query, &weights);
+            result.score = breakdown.total;
+            result.flags = breakdown.flags;
+            if self.config.expose_raw_score {
+                result.raw_score = Some(result.score);
+            }
+            if self.config.merge_mode == MergeMode::Weighted {
+                result.score =
+                    ResultScorer::apply_engine_weight(result, &self.config.engine_weights);
+            }
+        }
+    }
+
+    // Re-rank `query`'s results with another scorer, leaving the live one untouched.
+    // Each engine's page is taken as fetched, so nothing the live config filtered, merged or
+    // ranked carries over: results are scored (positions being the engines' own), sorted and
+    // deduplicated with the default dedup settings. Pages are cached for the next preview.
+    async fn score_preview(&self, query: &str, scorer: &ResultScorer) -> Vec<ScoredResult> {
+        let parsed_query = parse_query(query);
+        let scoring_query = if parsed_query.terms.is_empty() { query } else { &parsed_query.terms };
+
+        let mut results = self.preview_results(query, &parsed_query).await;
+        let weights = scorer.config().term_weights(&results, scoring_query);
+        for result in &mut results {
+            result.score = scorer.score_result(result, scoring_query, &weights);
+        }
+        results.sort();
+        let results = ResultScorer::remove_duplicates(
+            results,
+            DedupScope::default(),
+            false,
+            TitleCopies::default(),
+            None,
+            &DuplicateTitle::default(),
+            scoring_query,
+        );
+
+        // Merging copies may have changed the kept result's title, so it's scored again
+        let mut scored: Vec<ScoredResult> = results
+            .into_iter()
+            .map(|mut result| {
+                let mut breakdown = scorer.score_breakdown(&result, scoring_query, &weights);
+                result.score = breakdown.total;
+                result.flags = std::mem::take(&mut breakdown.flags);
+                ScoredResult { result, breakdown }
+            })
+            .collect();
+
+        scored.sort_by(|a, b| a.result.cmp(&b.result));
+        for (index, scored) in scored.iter_mut().enumerate() {
+            scored.result.rank = index + 1;
+        }
+        scored
+    }
+
+    // Every engine's first page for `query`, concatenated, with `rank` holding the position
+    // within the engine and AMP links unwrapped so copies can be deduplicated
+    async fn preview_results(&self, query: &str, parsed_query: &ParsedQuery) -> Vec<SearchResult> {
+        let defaults = &self.config.search_defaults;
+        let (date_range, region, language) = (
+            defaults.date_range.as_deref(),
+            defaults.region.as_deref(),
+            defaults.language.as_deref(),
+        );
+        let key = cache_key(
+            "score-preview",
+            &format!("{}:{:?}:{:?}:{:?}", query, date_range, region, language),
+        );
+        if let Some(results) = self.cache.get::<Vec<SearchResult>>(&key).await {
+            return results;
+        }
+
+        let (pages, _) = self
+            .query_engines(parsed_query, None, None, date_range, region, language)
+            .await;
+        let mut results = Vec::new();
+        for page in pages.into_iter().flatten() {
+            for (index, mut result) in page.results.into_iter().enumerate() {
+                result.rank = index + 1;
+                if let Some(canonical) = ResultScorer::de_amp(&result.link) {
+                    result.link = canonical;
+                }
+                results.push(result);
+            }
+        }
+
+        if !results.is_empty() {
+            let ttl = Duration::from_secs(self.config.cache_ttl_secs);
+            let _ = self.cache.set(&key, &results, ttl).await;
+        }
+        results
+    }
+
+    pub async fn autocomplete(&self, query: &str, client_id: Option<&str>) -> Vec<String> {
+        let mut suggestions = self.fetch_suggestions(query).await;
+        if self.config.fuzzy_autocomplete {
+            rank_suggestions(&mut suggestions, query);
+        }
+
+        let (Some(client_id), Some(_)) = (client_id, self.config.query_history_size) else {
+            return suggestions;
+        };
+
+        // The client's own matching queries come first, most recent first
+        let prefix = query.trim().to_lowercase();
+        let mut merged: Vec<String> = self
+            .query_history(client_id)
+            .await
+            .into_iter()
+            .filter(|past| past.to_lowercase().starts_with(&prefix))
+            .collect();
+        for suggestion in suggestions {
+            if !merged.iter().any(|past| past.eq_ignore_ascii_case(&suggestion)) {
+                merged.push(suggestion);
+            }
+        }
+
+        merged
+    }
+
+    // Remember a client's query for autocomplete, when query history is enabled
+    pub async fn record_query(&self, client_id: &str, query: &str) {
+        let Some(history_size) = self.config.query_history_size else {
+            return;
+        };
+
+        let query = normalize_query(query);
+        if query.is_empty() {
+            return;
+        }
+
+        let mut history = self.query_history(client_id).await;
+        history.retain(|past| !past.eq_ignore_ascii_case(&query));
+        history.insert(0, query);
+        history.truncate(history_size);
 
-        // Cache results
         let _ = self
             .cache
-            .set(&cache_key, &final_results, Duration::from_secs(300))
+            .set(
+                &cache_key("history", client_id),
+                &history,
+                Duration::from_secs(self.config.query_history_ttl_secs),
+            )
             .await;
+    }
 
-        final_results
+    async fn query_history(&self, client_id: &str) -> Vec<String> {
+        self.cache
+            .get(&cache_key("history", client_id))
+            .await
+            .unwrap_or_default()
     }
 
-    pub async fn autocomplete(&self, query: &str) -> Vec<String> {
-        let cache_key = format!("autocomplete:{}", query);
+    async fn fetch_suggestions(&self, query: &str) -> Vec<String> {
+        let started = Instant::now();
+        let cache_key = cache_key("autocomplete", query);
 
         if let Some(cached_results) = self.cache.get(&cache_key).await {
+            SearchMetrics::record_autocomplete(true, started.elapsed(), true);
             return cached_results;
         }
 
         let url = format!(
             "https://www.google.com/complete/search?q={}&cp=4&client=gws-wiz-serp&xssi=t&gs_pcrt=undefined&hl=fr&authuser=0&pq=google%20autocomplete%20search&psi=PT4yZ_aPFZmSkdUP8KizgQo.1731345982335&dpr=1&newwindow=1",
-            query
+            encode_query(query)
         );
 
-        let response = match reqwest::get(&url).await {
+        // On timeout we return no suggestions rather than stalling the endpoint
+        let response = match self.autocomplete_client.get(&url).send().await {
             Ok(resp) => resp,
             Err(err) => {
                 eprintln!("Request error: {:?}", err);
+                SearchMetrics::record_autocomplete(false, started.elapsed(), false);
                 return Vec::new();
             }
         };
@@ -184,6 +1215,7 @@ impl SearchService {
             Ok(text) => text,
             Err(err) => {
                 eprintln!("Response body error: {:?}", err);
+                SearchMetrics::record_autocomplete(false, started.elapsed(), false);
                 return Vec::new();
             }
         };
@@ -206,16 +1238,33 @@ impl SearchService {
             }
         }
 
+        let blocklist = &self.config.autocomplete_blocklist;
+        if !blocklist.is_empty() {
+            results.retain(|suggestion| !blocklist.is_blocked(suggestion));
+        }
+        results.truncate(self.config.autocomplete_cache_limit);
+
         let _ = self
             .cache
             .set(&cache_key, &results, Duration::from_secs(300))
             .await;
 
+        SearchMetrics::record_autocomplete(true, started.elapsed(), false);
         results
     }
 
-    pub async fn quick_answers(&self, query: &str) -> Vec<QuickAnswer> {
-        let cache_key = format!("quick_answers:{}", query);
+    // Quick answers from every engine that has one, most confident first; only the best one
+    // unless `all`
+    pub async fn quick_answers(&self, query: &str, all: bool) -> Vec<QuickAnswer> {
+        let mut answers = self.collect_quick_answers(query).await;
+        if !all {
+            answers.truncate(1);
+        }
+        answers
+    }
+
+    async fn collect_quick_answers(&self, query: &str) -> Vec<QuickAnswer> {
+        let cache_key = cache_key("quick_answers", query);
 
         // Check cache first
         if let Some(cached_result) = self.cache.get(&cache_key).await {
@@ -225,49 +1274,416 @@ impl SearchService {
 
         SearchMetrics::record_cache_miss();
 
-        let mut answers = Vec::new();
-
-        for engine in &self.engines {
+        let engine_answers = self.engines.iter().map(|engine| async move {
             if !self.rate_limiter.check_rate_limit(engine.name()).await {
-                continue;
+                return None;
             }
 
-            if let Ok(Some(answer)) = engine.quick_answer(query).await {
-                answers.push(answer);
-            }
-        }
+            let mut answer = engine.quick_answer(query).await.ok()??;
+            // Whatever the engine wrote, `source` says which engine answered
+            answer.source = engine.name().to_string();
+            Some(answer)
+        });
+        let mut answers: Vec<QuickAnswer> =
+            join_all(engine_answers).await.into_iter().flatten().collect();
 
+        answers.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
         answers
     }
 }
 
+// Key of the extended-retention copy of a cached search
+fn stale_key(cache_key: &str) -> String {
+    format!("{}:stale", cache_key)
+}
+
+// How many of the (ranked) results fit in `max_bytes` of JSON, counting each one's serialized
+// size plus its separator. The rest of the response is small next to them and isn't counted.
+fn fit_response_size(results: &[SearchResult], max_bytes: usize) -> usize {
+    let mut total = 0;
+    results
+        .iter()
+        .take_while(|result| {
+            total += serde_json::to_vec(result).map_or(0, |json| json.len()) + 1;
+            total <= max_bytes
+        })
+        .count()
+}
+
 // Rename the handler function to avoid conflict with the `search` crate or module.
 async fn handle_search(
     State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request_headers: HeaderMap,
     Query(params): Query<SearchParams>,
-) -> AppJson<Vec<SearchResult>> {
+) -> Result<(HeaderMap, SearchReply), AppError> {
+    let started = Instant::now();
+    let client = client_ip(addr, &request_headers, &state.config);
+    if let Some(limiter) = &state.client_limiter {
+        limiter.check(client).map_err(AppError::RateLimited)?;
+    }
+
     let search_service = state.search_service.clone();
+    let params = params.with_defaults(&state.config.search_defaults);
+    let page = check_page(params.page, &state.config)?;
 
-    AppJson(
-        search_service
-            .search(
-                &params.query,
-                params.page,
-                params.date_range.as_deref(),
-                params.region.as_deref(),
-                params.language.as_deref(),
-            )
-            .await,
-    )
+    // Explicit params win, then the client's GeoIP guess, then the configured defaults
+    let geo = state
+        .geoip
+        .as_ref()
+        .and_then(|geoip| geoip.lookup(client))
+        .unwrap_or_default();
+    // Results localized from the client's IP are personal and shouldn't be shared by caches
+    let personalized = (params.region.is_none() && geo.region.is_some())
+        || (params.language.is_none() && geo.language.is_some());
+    let region = params
+        .region
+        .or(geo.region)
+        .or_else(|| state.config.search_defaults.region.clone());
+    let language = params
+        .language
+        .or(geo.language)
+        .or_else(|| state.config.search_defaults.language.clone());
+
+    let do_not_log = do_not_log(&request_headers);
+    let search = search_service
+        .search(
+            &params.query,
+            page,
+            params.date_range.as_deref(),
+            region.as_deref(),
+            language.as_deref(),
+            params.scoring.unwrap_or(true),
+        )
+        .instrument(request_span("search", &params.query, do_not_log, &state.config));
+    let deadline = request_deadline(&request_headers, &state.config);
+    let mut outcome = SEARCH_DEADLINE.scope(deadline, search).await;
+    if params.verify_favicons {
+        search_service.verify_favicons(&mut outcome.results).await;
+    }
+    // Flags are kept with cached results, so they're dropped here rather than at scoring time
+    if !params.explain {
+        for result in &mut outcome.results {
+            result.flags.clear();
+        }
+    }
+
+    // Opting out of logging also keeps the query out of the client's history
+    if let Some(client_id) = client_id(&request_headers).filter(|_| !do_not_log) {
+        search_service.record_query(client_id, &params.query).await;
+    }
+
+    let mut headers = HeaderMap::new();
+    if let Some(value) = cache_control(&outcome, personalized, state.config.cache_control) {
+        headers.insert(header::CACHE_CONTROL, value);
+    }
+
+    Ok((
+        headers,
+        SearchReply {
+            format: params.format,
+            response: SearchResponse {
+                query: params.query,
+                page: page.unwrap_or(1),
+                took_ms: started.elapsed().as_millis() as u64,
+                cached: outcome.cached,
+                stale: outcome.stale,
+                results: outcome.results,
+                people_also_ask: outcome.people_also_ask,
+                featured_snippet: outcome.featured_snippet,
+                intent: outcome.intent,
+                exhausted_engines: outcome.exhausted_engines,
+                empty_pages: outcome.empty_pages,
+                engine_timings: params.debug.then_some(outcome.engine_timings),
+            },
+        },
+    ))
+}
+
+// `/api/search` response in the requested format
+struct SearchReply {
+    format: ResponseFormat,
+    response: SearchResponse,
+}
+
+impl IntoResponse for SearchReply {
+    fn into_response(self) -> Response {
+        match self.format {
+            ResponseFormat::Json => AppJson(self.response).into_response(),
+            ResponseFormat::Csv => match results_csv(&self.response.results) {
+                Ok(csv) => {
+                    ([(header::CONTENT_TYPE, "text/csv; charset=utf-8")], csv).into_response()
+                }
+                Err(e) => {
+                    eprintln!("Failed to write CSV results: {}", e);
+                    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+                }
+            },
+        }
+    }
+}
+
+// One row per result with a header row; the writer quotes fields holding commas, quotes or
+// newlines
+fn results_csv(results: &[SearchResult]) -> Result<Vec<u8>, csv::Error> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(["title", "link", "snippet", "source", "score"])?;
+    for result in results {
+        writer.write_record([
+            result.title.as_str(),
+            &result.link,
+            &result.snippet,
+            &result.source,
+            &result.score.to_string(),
+        ])?;
+    }
+
+    writer.into_inner().map_err(|e| e.into_error().into())
+}
+
+// Read a `PRELOAD_CACHE_FILE`: a JSON object of `"query": [results]`. Results only need
+// `title` and `link`, other fields default.
+fn load_preload_file(path: &str) -> Option<HashMap<String, Vec<SearchResult>>> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", path, e);
+            return None;
+        }
+    };
+
+    match serde_json::from_str(&content) {
+        Ok(entries) => Some(entries),
+        Err(e) => {
+            eprintln!("Invalid preload file {} (expected {{\"query\": [results]}}): {}", path, e);
+            None
+        }
+    }
+}
+
+// `Cache-Control` for a search response: shared caches may keep it for as long as our own
+// cache does, except for stale, uncached or personalized results
+fn cache_control(
+    outcome: &SearchOutcome,
+    personalized: bool,
+    policy: CacheControlPolicy,
+) -> Option<HeaderValue> {
+    let no_store = HeaderValue::from_static("no-store");
+
+    match policy {
+        CacheControlPolicy::Off => None,
+        CacheControlPolicy::NoStore => Some(no_store),
+        CacheControlPolicy::Auto => {
+            let remaining = outcome.expires_at.saturating_sub(unix_now());
+            if outcome.stale || personalized || remaining == 0 {
+                return Some(no_store);
+            }
+
+            HeaderValue::from_str(&format!("public, max-age={}", remaining)).ok()
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+// Debug endpoint: results grouped by engine, before merging and dedup (admin only)
+async fn handle_compare(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<SearchParams>,
+) -> Result<AppJson<CompareResponse>, AppError> {
+    require_admin(&headers, &state.config)?;
+    let started = Instant::now();
+    let params = params.with_defaults(&state.config.search_defaults);
+    let page = check_page(params.page, &state.config)?;
+    let region = params.region.or_else(|| state.config.search_defaults.region.clone());
+    let language = params.language.or_else(|| state.config.search_defaults.language.clone());
+
+    let engines = state
+        .search_service
+        .compare(
+            &params.query,
+            page,
+            params.date_range.as_deref(),
+            region.as_deref(),
+            language.as_deref(),
+        )
+        .await;
+
+    Ok(AppJson(CompareResponse {
+        query: params.query,
+        took_ms: started.elapsed().as_millis() as u64,
+        engines,
+    }))
+}
+
+// Try a scoring config against real results before applying it (admin only)
+async fn handle_score_preview(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    AppJson(request): AppJson<ScorePreviewRequest>,
+) -> Result<AppJson<ScorePreviewResponse>, AppError> {
+    require_admin(&headers, &state.config)?;
+
+    let scorer = ResultScorer::new(request.config);
+    let results = state.search_service.score_preview(&request.query, &scorer).await;
+
+    Ok(AppJson(ScorePreviewResponse {
+        query: request.query,
+        results,
+    }))
+}
+
+// List cached keys matching a glob, one page per call (admin only)
+async fn handle_cache_keys(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<CacheKeysParams>,
+) -> Result<AppJson<CacheKeysResponse>, AppError> {
+    require_admin(&headers, &state.config)?;
+
+    let (cursor, keys) = state
+        .search_service
+        .cache
+        .scan(&params.pattern, params.cursor)
+        .await
+        .map_err(|e| AppError::Internal(format!("cache scan failed: {}", e)))?;
+
+    Ok(AppJson(CacheKeysResponse { keys, cursor }))
+}
+
+// Admin endpoints need `ADMIN_TOKEN`, sent as `Authorization: Bearer <token>` or
+// `X-Admin-Token`; they're disabled when no token is configured
+fn require_admin(headers: &HeaderMap, config: &Config) -> Result<(), AppError> {
+    let Some(expected) = config.admin_token.as_deref() else {
+        return Err(AppError::Unauthorized("admin endpoints are disabled".to_string()));
+    };
+
+    let provided = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .or_else(|| {
+            headers
+                .get("x-admin-token")
+                .and_then(|value| value.to_str().ok())
+        });
+
+    match provided {
+        Some(token) if token.trim() == expected => Ok(()),
+        _ => Err(AppError::Unauthorized("invalid admin token".to_string())),
+    }
+}
+
+// Reject (or clamp, depending on config) pages beyond the configured maximum
+fn check_page(page: Option<u32>, config: &Config) -> Result<Option<u32>, AppError> {
+    match page {
+        Some(page) if page > config.max_page => {
+            if config.clamp_page {
+                Ok(Some(config.max_page))
+            } else {
+                Err(AppError::BadRequest(format!(
+                    "page must not exceed {}",
+                    config.max_page
+                )))
+            }
+        }
+        page => Ok(page),
+    }
+}
+
+// Reject (or truncate, depending on config) autocomplete queries beyond the maximum length
+fn check_autocomplete_query<'a>(query: &'a str, config: &Config) -> Result<&'a str, AppError> {
+    let max_length = config.autocomplete_max_query_length;
+    match query.char_indices().nth(max_length) {
+        Some((end, _)) if config.truncate_autocomplete_query => Ok(&query[..end]),
+        Some(_) => Err(AppError::BadRequest(format!(
+            "query must not exceed {} characters",
+            max_length
+        ))),
+        None => Ok(query),
+    }
 }
 
 async fn handle_autocomplete(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Query(params): Query<AutocompleteParams>,
-) -> AppJson<Vec<String>> {
+) -> Result<AppJson<Vec<String>>, AppError> {
     let search_service = state.search_service.clone();
+    let query = check_autocomplete_query(&params.query, &state.config)?;
+
+    let span = request_span("autocomplete", query, do_not_log(&headers), &state.config);
+
+    Ok(AppJson(
+        search_service
+            .autocomplete(query, client_id(&headers))
+            .instrument(span)
+            .await,
+    ))
+}
+
+// The client's address, or the one our reverse proxy saw when it's trusted to say so
+fn client_ip(addr: SocketAddr, headers: &HeaderMap, config: &Config) -> IpAddr {
+    let forwarded = || {
+        headers
+            .get("x-forwarded-for")?
+            .to_str()
+            .ok()?
+            .rsplit(',')
+            .next()?
+            .trim()
+            .parse()
+            .ok()
+    };
+
+    config.trust_forwarded_for.then(forwarded).flatten().unwrap_or(addr.ip())
+}
+
+// `X-Do-Not-Log: 1` keeps this request's query out of spans and query history
+fn do_not_log(headers: &HeaderMap) -> bool {
+    headers
+        .get("x-do-not-log")
+        .is_some_and(|value| value.as_bytes() == b"1")
+}
+
+// Span for a request, with the query recorded as `LOG_QUERIES` and the client allow
+fn request_span(endpoint: &'static str, query: &str, do_not_log: bool, config: &Config) -> Span {
+    let span = tracing::info_span!("request", endpoint, query = tracing::field::Empty);
+    if let Some(query) = config.query_logging.loggable(query, do_not_log) {
+        span.record("query", query);
+    }
+
+    span
+}
+
+// When the client wants the search over by, `X-Request-Timeout-Ms` from now capped at
+// `max_request_timeout_ms`; no deadline without the header
+fn request_deadline(headers: &HeaderMap, config: &Config) -> Option<tokio::time::Instant> {
+    let timeout_ms: u64 = headers
+        .get(REQUEST_TIMEOUT_HEADER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
 
-    AppJson(search_service.autocomplete(&params.query).await)
+    let timeout = Duration::from_millis(timeout_ms.min(config.max_request_timeout_ms));
+    Some(tokio::time::Instant::now() + timeout)
+}
+
+// Opaque client identifier used to key per-client query history
+fn client_id(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(CLIENT_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|id| !id.is_empty() && id.len() <= 128)
 }
 
 // Handler pour les quick answers
@@ -276,23 +1692,83 @@ async fn handle_quick_answers(
     Query(params): Query<QuickAnswerParams>,
 ) -> AppJson<Vec<QuickAnswer>> {
     let search_service = state.search_service.clone();
-    AppJson(search_service.quick_answers(&params.query).await)
+    AppJson(search_service.quick_answers(&params.query, params.all).await)
 }
 
-#[tokio::main]
-async fn main() {
-    // Initialize Redis cache
+async fn handle_engines(State(state): State<AppState>) -> AppJson<EnginesResponse> {
+    AppJson(EnginesResponse {
+        engines: state.search_service.engine_statuses(),
+    })
+}
+
+// Prometheus scrape endpoint; only there with `METRICS_BACKEND=prometheus`
+async fn handle_metrics(State(state): State<AppState>) -> Response {
+    match &state.prometheus {
+        Some(handle) => handle.render().into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+fn main() {
     dotenv::dotenv().ok();
-    let redis_url = std::env::var("REDIS_URL").expect("REDIS_URL must be set");
+    let config = Config::from_env();
 
-    let cache = match RedisCache::new(redis_url.as_str()).await {
-        Ok(cache) => cache,
+    // Size the runtime explicitly so small containers can match their CPU quota
+    let mut runtime = tokio::runtime::Builder::new_multi_thread();
+    if let Some(threads) = config.worker_threads {
+        runtime.worker_threads(threads);
+    }
+    let runtime = match runtime.enable_all().build() {
+        Ok(runtime) => runtime,
         Err(e) => {
-            eprintln!("Failed to connect to Redis: {}", e);
+            eprintln!("Failed to start the tokio runtime: {}", e);
             return;
         }
     };
 
+    runtime.block_on(run(config));
+}
+
+async fn run(config: Config) {
+    // Exporters need the runtime, and stay alive (and get flushed) for as long as `run`
+    let mut prometheus = None;
+    let _telemetry = match config.metrics_backend {
+        MetricsBackend::Otlp => match telemetry::init_otlp(&config.otlp_endpoint) {
+            Ok(telemetry) => Some(telemetry),
+            Err(e) => {
+                eprintln!("Failed to set up OTLP export to {}: {}", config.otlp_endpoint, e);
+                None
+            }
+        },
+        MetricsBackend::Prometheus => {
+            match telemetry::init_prometheus() {
+                Ok(handle) => prometheus = Some(handle),
+                Err(e) => eprintln!("Failed to set up Prometheus metrics: {}", e),
+            }
+            None
+        }
+        MetricsBackend::None => None,
+    };
+
+    // Redis when configured, otherwise a process-local cache so the server runs without it
+    let cache: Arc<dyn Cache> = match std::env::var("REDIS_URL") {
+        Ok(redis_url) => match RedisCache::new(redis_url.as_str()).await {
+            Ok(cache) => Arc::new(
+                cache
+                    .with_compression_threshold(config.cache_compression_threshold)
+                    .with_max_concurrent_ops(config.redis_max_concurrent_ops),
+            ),
+            Err(e) => {
+                eprintln!("Failed to connect to Redis: {}", e);
+                return;
+            }
+        },
+        Err(_) => {
+            println!("REDIS_URL not set, caching in memory");
+            Arc::new(InMemoryCache::new())
+        }
+    };
+
     // Clear cache
     match cache.flush().await {
         Ok(_) => (),
@@ -300,15 +1776,31 @@ async fn main() {
     }
 
     // Initialize SearchService and wrap it in AppState
-    let search_service = Arc::new(SearchService::new(cache));
-    let app_state = AppState { search_service };
+    let config = Arc::new(config);
 
-    let router = Router::new()
-        .route("/api/search", get(handle_search))
-        .route("/api/quick-answers", get(handle_quick_answers))
-        .route("/api/autocomplete", get(handle_autocomplete))
-        .layer(CorsLayer::permissive())
-        .fallback_service(ServeDir::new("dist"));
+    // GeoIP is optional: without a database we fall back to the configured defaults
+    let geoip = config
+        .geoip_db_path
+        .as_ref()
+        .and_then(|path| match GeoIp::open(path) {
+            Ok(geoip) => Some(Arc::new(geoip)),
+            Err(e) => {
+                eprintln!("Failed to open GeoIP database {}: {}", path, e);
+                None
+            }
+        });
+
+    let search_service = Arc::new(SearchService::new(cache, config.clone()));
+    if let Some(path) = &config.preload_cache_file {
+        search_service.preload_cache(path).await;
+    }
+    let app_state = AppState {
+        search_service,
+        client_limiter: ClientRateLimiter::per_minute(config.client_rate_limit).map(Arc::new),
+        prometheus,
+        config,
+        geoip,
+    };
 
     let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
     let listener = match tokio::net::TcpListener::bind(&addr).await {
@@ -318,7 +1810,9 @@ async fn main() {
             return;
         }
     };
-    match axum::serve(listener, router.with_state(app_state).into_make_service()).await {
+    match axum::serve(listener, router()
+            .with_state(app_state)
+            .into_make_service_with_connect_info::<SocketAddr>()).await {
         Ok(_) => println!("Server running on http://localhost:3000"),
         Err(e) => eprintln!("Failed to start server: {}", e)
     };
@@ -326,6 +1820,20 @@ async fn main() {
     println!("Server running on http://localhost:3000");
 }
 
+fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/search", get(handle_search))
+        .route("/api/quick-answers", get(handle_quick_answers))
+        .route("/api/autocomplete", get(handle_autocomplete))
+        .route("/api/compare", get(handle_compare))
+        .route("/api/engines", get(handle_engines))
+        .route("/metrics", get(handle_metrics))
+        .route("/api/admin/score-preview", post(handle_score_preview))
+        .route("/api/admin/cache/keys", get(handle_cache_keys))
+        .layer(CorsLayer::permissive())
+        .fallback_service(ServeDir::new("dist"))
+}
+
 #[derive(FromRequest)]
 #[from_request(via(axum::Json), rejection(AppError))]
 struct AppJson<T>(T);
@@ -343,8 +1851,14 @@ where
 enum AppError {
     // The request body contained invalid JSON
     JsonRejection(JsonRejection),
-    // Some error from a third party library we're using
-    TimeError(time_library::Error),
+    // The request parameters were invalid
+    BadRequest(String),
+    // Missing or wrong admin credentials
+    Unauthorized(String),
+    // A backend (e.g. Redis) failed
+    Internal(String),
+    // The client is over its request quota, and may retry after this long
+    RateLimited(Duration),
 }
 
 // Tell axum how `AppError` should be converted into a response.
@@ -359,20 +1873,28 @@ impl IntoResponse for AppError {
         }
 
         let (status, message) = match self {
+            AppError::RateLimited(retry_after) => {
+                let message = ErrorResponse {
+                    message: "too many requests".to_string(),
+                };
+                // Whole seconds, rounded up so a retry right on time isn't refused again
+                let seconds = (retry_after.as_millis() as u64).div_ceil(1000).max(1);
+                return (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(header::RETRY_AFTER, seconds.to_string())],
+                    AppJson(message),
+                )
+                    .into_response();
+            }
             AppError::JsonRejection(rejection) => {
                 // This error is caused by bad user input so don't log it
                 (rejection.status(), rejection.body_text())
             }
-            AppError::TimeError(err) => {
-                // Because `TraceLayer` wraps each request in a span that contains the request
-                // method, uri, etc we don't need to include those details here
-                tracing::error!(%err, "error from time_library");
-
-                // Don't expose any details about the error to the client
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "Something went wrong".to_owned(),
-                )
+            AppError::BadRequest(message) => (StatusCode::BAD_REQUEST, message),
+            AppError::Unauthorized(message) => (StatusCode::UNAUTHORIZED, message),
+            AppError::Internal(message) => {
+                eprintln!("{}", message);
+                (StatusCode::INTERNAL_SERVER_ERROR, message)
             }
         };
 
@@ -386,28 +1908,83 @@ impl From<JsonRejection> for AppError {
     }
 }
 
-impl From<time_library::Error> for AppError {
-    fn from(error: time_library::Error) -> Self {
-        Self::TimeError(error)
-    }
-}
+// The server itself lives in this binary, so tests that go through `SearchService` and the
+// router are here rather than in `tests/`
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use tower::ServiceExt;
 
-// Imagine this is some third party library that we're using. It sometimes returns errors which we
-// want to log.
-mod time_library {
-    use serde::Serialize;
+    const GOOGLE_PAGE: &str = r#"<html><body>
+        <div class="g"><a href="https://www.rust-lang.org/"><h3>Rust Programming Language</h3></a>
+            <div class="VwiC3b">A language empowering everyone to build reliable software.</div>
+        </div>
+        <div class="g"><a href="https://doc.rust-lang.org/book/"><h3>The Rust Book</h3></a>
+            <div class="VwiC3b">An introductory book about Rust.</div>
+        </div>
+    </body></html>"#;
 
-    #[derive(Serialize, Clone)]
-    pub struct Timestamp(u64);
+    // Every engine is pointed at a local server answering Google's results page, which only
+    // Google's parser finds results in
+    async fn stub_engines() {
+        let stub = Router::new().fallback(|| async { axum::response::Html(GOOGLE_PAGE) });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}/search", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, stub).await });
 
-    #[derive(Debug)]
-    pub enum Error {
-        FailedToGetTime,
+        let vars = [
+            "GOOGLE_BASE_URL",
+            "DUCKDUCKGO_BASE_URL",
+            "BRAVE_BASE_URL",
+            "STARTPAGE_BASE_URL",
+        ];
+        for var in vars {
+            std::env::set_var(var, &base_url);
+        }
+    }
+
+    async fn body_text(response: Response) -> String {
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
     }
 
-    impl std::fmt::Display for Error {
-        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-            write!(f, "failed to get time")
+    // The recorder is process-wide and can only be installed once, so this is the only test
+    // installing it
+    #[tokio::test]
+    async fn metrics_endpoint_reports_a_search() {
+        stub_engines().await;
+        let config = Arc::new(Config::default());
+        let state = AppState {
+            search_service: Arc::new(SearchService::new(
+                Arc::new(InMemoryCache::new()),
+                config.clone(),
+            )),
+            client_limiter: None,
+            prometheus: Some(telemetry::init_prometheus().unwrap()),
+            config,
+            geoip: None,
+        };
+        let app = router().with_state(state);
+
+        let mut search = Request::get("/api/search?query=rust").body(Body::empty()).unwrap();
+        search.extensions_mut().insert(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))));
+        let response = app.clone().oneshot(search).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(body_text(response).await.contains("The Rust Book"));
+
+        let metrics = Request::get("/metrics").body(Body::empty()).unwrap();
+        let response = app.oneshot(metrics).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let rendered = body_text(response).await;
+        for metric in [
+            "search_duration_seconds_count{engine=\"Google\"} 1",
+            "search_total{engine=\"Google\",success=\"true\"} 1",
+            "search_results_count{engine=\"Google\"} 2",
+            "cache_misses_total 1",
+        ] {
+            assert!(rendered.contains(metric), "{} missing from:\n{}", metric, rendered);
         }
     }
 }
@@ -1,30 +1,53 @@
 use metrics::{counter, gauge, histogram};
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 pub struct SearchMetrics;
 
 impl SearchMetrics {
     // Record timing for a search operation
     pub fn record_search_time(engine: &str, duration: Duration) {
-        // histogram!("search_duration_seconds", duration.as_secs_f64(), "engine" => engine.to_string());
+        histogram!("search_duration_seconds", "engine" => engine.to_string())
+            .record(duration.as_secs_f64());
     }
 
     // Record success/failure of search operations
     pub fn record_search_result(engine: &str, success: bool) {
-        counter!("search_total", "engine" => engine.to_string(), "success" => success.to_string());
+        counter!("search_total", "engine" => engine.to_string(), "success" => success.to_string()).increment(1);
+    }
+
+    // Share of the engine's recent searches that succeeded (see `EngineHealth`)
+    pub fn record_engine_success_rate(engine: &str, rate: f64) {
+        gauge!("engine_success_rate", "engine" => engine.to_string()).set(rate);
     }
 
     // Record number of results returned
     pub fn record_results_count(engine: &str, count: u64) {
-        // gauge!("search_results_count", count as f64, "engine" => engine.to_string());
+        gauge!("search_results_count", "engine" => engine.to_string()).set(count as f64);
     }
 
     // Record cache operations
     pub fn record_cache_hit() {
-        counter!("cache_hits_total");
+        counter!("cache_hits_total").increment(1);
     }
 
     pub fn record_cache_miss() {
-        counter!("cache_misses_total");
+        counter!("cache_misses_total").increment(1);
+    }
+
+    // A cached value that no longer deserializes, e.g. after a schema change; it gets deleted
+    pub fn record_cache_deserialize_error() {
+        counter!("cache_deserialize_errors_total").increment(1);
+    }
+
+    // Record an autocomplete request: outcome, latency and whether it was served from cache
+    pub fn record_autocomplete(success: bool, duration: Duration, cached: bool) {
+        counter!(
+            "autocomplete_total",
+            "success" => success.to_string(),
+            "cached" => cached.to_string()
+        )
+        .increment(1);
+        histogram!("autocomplete_duration_seconds", "cached" => cached.to_string())
+            .record(duration.as_secs_f64());
     }
 }
\ No newline at end of file
@@ -0,0 +1,407 @@
+use chrono::NaiveDate;
+use regex::{Regex, RegexBuilder};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use strsim::normalized_levenshtein;
+
+// Normalize a raw user query before it reaches the cache key and the engines:
+// - leading/trailing whitespace is trimmed
+// - runs of whitespace (tabs, newlines, repeated spaces) collapse to one space
+// - an unbalanced `"` is dropped, so a stray quote can't swallow the rest of the query
+// Balanced quotes are kept as-is since engines use them for phrase search.
+pub fn normalize_query(query: &str) -> String {
+    let mut normalized = query.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    if normalized.matches('"').count() % 2 == 1 {
+        if let Some(index) = normalized.rfind('"') {
+            normalized.remove(index);
+            normalized = normalized.split_whitespace().collect::<Vec<_>>().join(" ");
+        }
+    }
+
+    normalized
+}
+
+// Search operators we understand and validate before passing them to engines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperatorKind {
+    Site,
+    FileType,
+    InTitle,
+    InUrl,
+}
+
+impl OperatorKind {
+    pub const ALL: &'static [OperatorKind] = &[
+        OperatorKind::Site,
+        OperatorKind::FileType,
+        OperatorKind::InTitle,
+        OperatorKind::InUrl,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OperatorKind::Site => "site",
+            OperatorKind::FileType => "filetype",
+            OperatorKind::InTitle => "intitle",
+            OperatorKind::InUrl => "inurl",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "site" => Some(OperatorKind::Site),
+            "filetype" | "ext" => Some(OperatorKind::FileType),
+            "intitle" => Some(OperatorKind::InTitle),
+            "inurl" => Some(OperatorKind::InUrl),
+            _ => None,
+        }
+    }
+
+    // Normalize an operator value, or reject it when it's malformed
+    fn normalize_value(&self, value: &str) -> Option<String> {
+        let value = value.trim();
+        let normalized = match self {
+            OperatorKind::Site => {
+                let site = value
+                    .trim_start_matches("https://")
+                    .trim_start_matches("http://")
+                    .trim_end_matches('/')
+                    .to_ascii_lowercase();
+                let valid = site
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '/'));
+                valid.then_some(site)?
+            }
+            OperatorKind::FileType => {
+                let extension = value.trim_start_matches('.').to_ascii_lowercase();
+                let valid = extension.len() <= 5
+                    && extension.chars().all(|c| c.is_ascii_alphanumeric());
+                valid.then_some(extension)?
+            }
+            OperatorKind::InTitle | OperatorKind::InUrl => value.to_string(),
+        };
+
+        (!normalized.is_empty()).then_some(normalized)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Operator {
+    pub kind: OperatorKind,
+    pub value: String,
+    // `-site:example.com` excludes instead of restricting
+    pub negated: bool,
+}
+
+impl Operator {
+    fn render(&self) -> String {
+        let value = if self.value.contains(char::is_whitespace) {
+            format!("\"{}\"", self.value)
+        } else {
+            self.value.clone()
+        };
+
+        format!(
+            "{}{}:{}",
+            if self.negated { "-" } else { "" },
+            self.kind.name(),
+            value
+        )
+    }
+}
+
+// Date bounds from `after:YYYY-MM-DD` / `before:YYYY-MM-DD`, passed to engines as parameters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DateFilter {
+    pub after: Option<NaiveDate>,
+    pub before: Option<NaiveDate>,
+}
+
+impl DateFilter {
+    pub fn is_empty(&self) -> bool {
+        self.after.is_none() && self.before.is_none()
+    }
+
+    // Smallest `d`/`w`/`m`/`y` window reaching back to `after`, for engines that only have
+    // relative ranges. Ranges with an end date or starting over a year ago have no equivalent.
+    pub fn approximate_range(&self, today: NaiveDate) -> Option<&'static str> {
+        if self.before.is_some() {
+            return None;
+        }
+
+        match (today - self.after?).num_days() {
+            days if days <= 1 => Some("d"),
+            days if days <= 7 => Some("w"),
+            days if days <= 31 => Some("m"),
+            days if days <= 366 => Some("y"),
+            _ => None,
+        }
+    }
+
+    fn parse_date(value: &str) -> Option<NaiveDate> {
+        NaiveDate::parse_from_str(value.trim_matches('"'), "%Y-%m-%d").ok()
+    }
+}
+
+// A query split into its free-text terms and its recognized operators
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParsedQuery {
+    pub terms: String,
+    pub operators: Vec<Operator>,
+    pub date_filter: DateFilter,
+}
+
+impl ParsedQuery {
+    // Rebuild the query for an engine, keeping only the operators it supports
+    pub fn to_query_string(&self, supported: &[OperatorKind]) -> String {
+        std::iter::once(self.terms.clone())
+            .chain(
+                self.operators
+                    .iter()
+                    .filter(|operator| supported.contains(&operator.kind))
+                    .map(Operator::render),
+            )
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+// Split a query into terms and operators. Quoted phrases are kept intact (operators inside
+// them are plain text), operator names are case-insensitive, and malformed operators such as
+// `site:` with no value or `filetype:???` are dropped rather than sent to engines.
+// `after:`/`before:` always leave the query text and become its date filter when valid.
+pub fn parse_query(query: &str) -> ParsedQuery {
+    let mut terms = Vec::new();
+    let mut operators = Vec::new();
+    let mut date_filter = DateFilter::default();
+
+    let mut tokens = tokenize(query).into_iter();
+    while let Some(mut token) = tokens.next() {
+        // `site: example.com` -> `site:example.com`
+        let name = token.trim_matches(['-', ':']);
+        let dangling = token.ends_with(':')
+            && (OperatorKind::from_name(name).is_some()
+                || name.eq_ignore_ascii_case("after")
+                || name.eq_ignore_ascii_case("before"));
+        if dangling {
+            if let Some(value) = tokens.next() {
+                token.push_str(&value);
+            }
+        }
+
+        if token.starts_with('"') {
+            terms.push(token);
+            continue;
+        }
+
+        let (negated, body) = match token.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, token.as_str()),
+        };
+
+        if let Some((name, value)) = body.split_once(':') {
+            let date = match name.to_ascii_lowercase().as_str() {
+                "after" => Some(&mut date_filter.after),
+                "before" => Some(&mut date_filter.before),
+                _ => None,
+            };
+            if let Some(date) = date {
+                // Negated date bounds have no engine equivalent and are dropped too
+                if !negated {
+                    if let Some(parsed) = DateFilter::parse_date(value) {
+                        *date = Some(parsed);
+                    }
+                }
+                continue;
+            }
+        }
+
+        let operator = body.split_once(':').and_then(|(name, value)| {
+            let kind = OperatorKind::from_name(name)?;
+            Some((kind, kind.normalize_value(value.trim_matches('"'))))
+        });
+
+        match operator {
+            Some((kind, Some(value))) => operators.push(Operator {
+                kind,
+                value,
+                negated,
+            }),
+            // Recognized operator with an unusable value
+            Some((_, None)) => {}
+            None => terms.push(token),
+        }
+    }
+
+    ParsedQuery {
+        terms: terms.join(" "),
+        operators,
+        date_filter,
+    }
+}
+
+// Whitespace-separated tokens, keeping `"quoted phrases"` (and `op:"quoted values"`) whole
+fn tokenize(query: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+
+    for c in query.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                current.push(c);
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    tokens
+}
+
+// Synonyms OR-ed into engine queries to improve recall: `cheap car` -> `cheap car OR automobile`.
+// Engines bind `OR` to its neighbours, so only the expanded term is widened.
+#[derive(Debug, Clone, Default)]
+pub struct Synonyms {
+    map: HashMap<String, Vec<String>>,
+    // Synonyms added per query, across all its terms
+    max: usize,
+}
+
+impl Synonyms {
+    pub fn new(map: HashMap<String, Vec<String>>, max: usize) -> Self {
+        Self {
+            map: map
+                .into_iter()
+                .map(|(word, synonyms)| (word.to_lowercase(), synonyms))
+                .collect(),
+            max,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty() || self.max == 0
+    }
+
+    // Expand free-text terms, earlier terms first until `max` synonyms are in. Phrases,
+    // excluded words and terms the user already OR-ed are left alone.
+    pub fn expand(&self, terms: &str) -> String {
+        let tokens = tokenize(terms);
+        let mut remaining = self.max;
+        let mut expanded = Vec::with_capacity(tokens.len());
+
+        for (index, token) in tokens.iter().enumerate() {
+            expanded.push(token.clone());
+
+            let next_to_or = [index.checked_sub(1), Some(index + 1)]
+                .into_iter()
+                .flatten()
+                .any(|neighbour| tokens.get(neighbour).is_some_and(|t| t == "OR"));
+            if remaining == 0 || next_to_or || token.starts_with(['"', '-']) || token == "OR" {
+                continue;
+            }
+
+            let Some(synonyms) = self.map.get(&token.to_lowercase()) else {
+                continue;
+            };
+            for synonym in synonyms.iter().filter(|s| !s.trim().is_empty()).take(remaining) {
+                let synonym = synonym.trim();
+                expanded.push("OR".to_string());
+                if synonym.contains(char::is_whitespace) {
+                    expanded.push(format!("\"{}\"", synonym));
+                } else {
+                    expanded.push(synonym.to_string());
+                }
+                remaining -= 1;
+            }
+        }
+
+        expanded.join(" ")
+    }
+}
+
+// Percent-encode a query for use as a URL query-string value
+pub fn encode_query(query: &str) -> String {
+    url::form_urlencoded::byte_serialize(query.as_bytes()).collect()
+}
+
+// Reorder autocomplete suggestions by closeness to what was typed: those starting with the
+// query come first, then longer shared prefixes, then smaller edit distance. Ties keep the
+// upstream order.
+pub fn rank_suggestions(suggestions: &mut [String], query: &str) {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return;
+    }
+
+    suggestions.sort_by_cached_key(|suggestion| {
+        let suggestion = suggestion.to_lowercase();
+        let shared_prefix = suggestion
+            .chars()
+            .zip(query.chars())
+            .take_while(|(a, b)| a == b)
+            .count();
+        // Similarity in thousandths, so the key stays `Ord`
+        let similarity = (normalized_levenshtein(&suggestion, &query) * 1000.0).round() as u32;
+
+        (
+            !suggestion.starts_with(&query),
+            Reverse(shared_prefix),
+            Reverse(similarity),
+        )
+    });
+}
+
+// Suggestions to hide from autocomplete. Plain entries match as case-insensitive substrings,
+// entries written `/like this/` are regular expressions.
+#[derive(Debug, Clone, Default)]
+pub struct Blocklist {
+    substrings: Vec<String>,
+    patterns: Vec<Regex>,
+}
+
+impl Blocklist {
+    // Invalid regular expressions are reported and skipped
+    pub fn from_entries(entries: &[String]) -> Self {
+        let mut blocklist = Self::default();
+
+        for entry in entries.iter().map(|entry| entry.trim()) {
+            let pattern = entry
+                .strip_prefix('/')
+                .and_then(|rest| rest.strip_suffix('/'))
+                .filter(|pattern| !pattern.is_empty());
+
+            match pattern {
+                Some(pattern) => match RegexBuilder::new(pattern).case_insensitive(true).build() {
+                    Ok(regex) => blocklist.patterns.push(regex),
+                    Err(e) => eprintln!("Invalid blocklist pattern {}: {}", entry, e),
+                },
+                None if !entry.is_empty() => blocklist.substrings.push(entry.to_lowercase()),
+                None => {}
+            }
+        }
+
+        blocklist
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.substrings.is_empty() && self.patterns.is_empty()
+    }
+
+    pub fn is_blocked(&self, text: &str) -> bool {
+        let lowercase = text.to_lowercase();
+
+        self.substrings.iter().any(|substring| lowercase.contains(substring))
+            || self.patterns.iter().any(|pattern| pattern.is_match(text))
+    }
+}
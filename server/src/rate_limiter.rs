@@ -1,46 +1,180 @@
+use chrono::{Local, NaiveTime};
 use governor::{
     Quota,
     RateLimiter as Governor,
-    clock::DefaultClock,
-    state::{InMemoryState, NotKeyed},
+    clock::{Clock, DefaultClock},
+    state::{keyed::DefaultKeyedStateStore, InMemoryState, NotKeyed},
 };
+use std::collections::HashMap;
+use std::net::IpAddr;
 use std::num::NonZeroU32;
-use std::sync::Arc;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+type EngineLimiters = HashMap<String, Arc<Governor<NotKeyed, InMemoryState, DefaultClock>>>;
+
+// Requests per second allowed to each engine
+const ENGINE_RATES: [(&str, u32); 4] =
+    [("Google", 5), ("DuckDuckGo", 5), ("Brave", 3), ("Startpage", 3)];
+
+// Rate multiplier for a daily time window, in local time, e.g. `08:00-20:00=0.5` to halve the
+// rate during the day. Windows ending before they start wrap past midnight.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub multiplier: f64,
+}
+
+impl RateWindow {
+    fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl FromStr for RateWindow {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid rate window {} (expected HH:MM-HH:MM=multiplier)", value);
+        let (range, multiplier) = value.split_once('=').ok_or_else(invalid)?;
+        let (start, end) = range.split_once('-').ok_or_else(invalid)?;
+        let time =
+            |time: &str| NaiveTime::parse_from_str(time.trim(), "%H:%M").map_err(|_| invalid());
+
+        Ok(Self {
+            start: time(start)?,
+            end: time(end)?,
+            multiplier: multiplier
+                .trim()
+                .parse()
+                .ok()
+                .filter(|multiplier: &f64| *multiplier > 0.0)
+                .ok_or_else(invalid)?,
+        })
+    }
+}
 
 pub struct RateLimiter {
-    limiters: std::collections::HashMap<String, Arc<Governor<NotKeyed, InMemoryState, DefaultClock>>>,
+    limiters: EngineLimiters,
+    // Limiters at each window's adjusted rate, used instead of the base ones inside the window
+    scheduled: Vec<(RateWindow, EngineLimiters)>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl RateLimiter {
     pub fn new() -> Self {
-                let mut limiters = std::collections::HashMap::new();
-
-                // Configure rate limits for each engine
-                limiters.insert(
-                        "Google".to_string(),
-                        Arc::new(Governor::new(
-                                Quota::per_second(NonZeroU32::new(5).unwrap()),
-                                InMemoryState::default(),
-                                DefaultClock::default(),
-                        )),
-                );
-                limiters.insert(
-                        "DuckDuckGo".to_string(),
-                        Arc::new(Governor::new(
-                                Quota::per_second(NonZeroU32::new(5).unwrap()),
-                                InMemoryState::default(),
-                                DefaultClock::default(),
-                        )),
-                );
+        Self {
+            limiters: Self::build_limiters(1.0),
+            scheduled: Vec::new(),
+        }
+    }
 
-        Self { limiters }
+    // Scale every engine's rate by the multiplier of the window the current time falls in; the
+    // first matching window wins
+    pub fn with_schedule(mut self, windows: Vec<RateWindow>) -> Self {
+        self.scheduled = windows
+            .into_iter()
+            .map(|window| {
+                let limiters = Self::build_limiters(window.multiplier);
+                (window, limiters)
+            })
+            .collect();
+        self
+    }
+
+    // Configure rate limits for each engine, scaled by `multiplier`. The scaled rate is kept
+    // exact, fractional or not, by spacing requests 1/rate seconds apart; bursts allow up to
+    // one second's worth, at least one request.
+    fn build_limiters(multiplier: f64) -> EngineLimiters {
+        ENGINE_RATES
+            .iter()
+            .map(|&(engine, rate)| {
+                let rate = rate as f64 * multiplier;
+                let burst = NonZeroU32::new(rate.floor() as u32).unwrap_or(NonZeroU32::MIN);
+                let period = Duration::from_secs_f64(1.0 / rate).max(Duration::from_nanos(1));
+                let quota = Quota::with_period(period)
+                    .unwrap()
+                    .allow_burst(burst);
+                let limiter = Governor::new(
+                    quota,
+                    InMemoryState::default(),
+                    DefaultClock::default(),
+                );
+                (engine.to_string(), Arc::new(limiter))
+            })
+            .collect()
     }
 
     pub async fn check_rate_limit(&self, engine: &str) -> bool {
-        if let Some(limiter) = self.limiters.get(engine) {
+        let now = Local::now().time();
+        let limiters = self
+            .scheduled
+            .iter()
+            .find(|(window, _)| window.contains(now))
+            .map_or(&self.limiters, |(_, limiters)| limiters);
+
+        if let Some(limiter) = limiters.get(engine) {
             limiter.check().is_ok()
         } else {
             true
         }
     }
 }
+
+// Clients tracked before idle ones (whose quota has fully refilled) are forgotten
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+// Forgetting them scans every client, so it runs at most this often. A client's quota takes a
+// minute to refill, sweeping sooner would rarely find more of them.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+// Inbound requests allowed per client IP and minute
+pub struct ClientRateLimiter {
+    limiter: Governor<IpAddr, DefaultKeyedStateStore<IpAddr>, DefaultClock>,
+    clock: DefaultClock,
+    last_sweep: Mutex<Instant>,
+}
+
+impl ClientRateLimiter {
+    // `None` when `per_minute` is 0, i.e. clients aren't limited
+    pub fn per_minute(per_minute: u32) -> Option<Self> {
+        let quota = Quota::per_minute(NonZeroU32::new(per_minute)?);
+        Some(Self {
+            limiter: Governor::keyed(quota),
+            clock: DefaultClock::default(),
+            last_sweep: Mutex::new(Instant::now()),
+        })
+    }
+
+    // How long the client must wait when it's over its quota
+    pub fn check(&self, client: IpAddr) -> Result<(), Duration> {
+        if self.limiter.len() > MAX_TRACKED_CLIENTS {
+            // Claim the sweep, then release the lock so other requests don't wait on it
+            let sweep = {
+                let mut last_sweep = self.last_sweep.lock().unwrap();
+                let due = last_sweep.elapsed() >= SWEEP_INTERVAL;
+                if due {
+                    *last_sweep = Instant::now();
+                }
+                due
+            };
+            if sweep {
+                self.limiter.retain_recent();
+            }
+        }
+
+        self.limiter
+            .check_key(&client)
+            .map_err(|not_until| not_until.wait_time_from(self.clock.now()))
+    }
+}
@@ -1,229 +1,1170 @@
-use std::{collections::HashSet, vec};
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use std::sync::LazyLock;
+use std::vec;
 
 use crate::scraper::SearchResult;
+use chrono::{DateTime, Utc};
+use publicsuffix::{List, Psl};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
 use strsim::normalized_levenshtein;
 use unidecode::unidecode;
 use url::Url;
 
-pub struct ResultScorer;
+// Mozilla's public suffix list, vendored from https://publicsuffix.org/list/public_suffix_list.dat
+static PUBLIC_SUFFIXES: LazyLock<List> = LazyLock::new(|| {
+    include_str!("../data/public_suffix_list.dat").parse().expect("invalid public suffix list")
+});
+
+// How results from the different engines are combined into the final ranking
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MergeMode {
+    // Rank purely on the heuristic score
+    #[default]
+    Score,
+    // Rank on `score * engine_weight + engine_priority_bonus`
+    Weighted,
+    // Alternate engines by their own ranking (every engine's #1, then every #2, ...), the
+    // score only ordering results of the same rank
+    Interleave,
+}
+
+impl FromStr for MergeMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "score" => Ok(MergeMode::Score),
+            "weighted" => Ok(MergeMode::Weighted),
+            "interleave" => Ok(MergeMode::Interleave),
+            other => Err(format!("unknown merge mode: {}", other)),
+        }
+    }
+}
+
+// Per-engine trust used by `MergeMode::Weighted`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EngineWeight {
+    pub weight: f64,
+    pub priority_bonus: f64,
+}
+
+impl Default for EngineWeight {
+    fn default() -> Self {
+        Self {
+            weight: 1.0,
+            priority_bonus: 0.0,
+        }
+    }
+}
+
+// Which results are compared when removing duplicates
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DedupScope {
+    // A URL appears once, whichever engines returned it
+    #[default]
+    Global,
+    // A URL appears once per engine, to compare engine coverage
+    PerEngine,
+}
+
+impl FromStr for DedupScope {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "global" => Ok(DedupScope::Global),
+            "per_engine" => Ok(DedupScope::PerEngine),
+            other => Err(format!("unknown dedup scope: {}", other)),
+        }
+    }
+}
+
+// Engine whose copy of a duplicated result should be kept, as long as its score is within
+// `margin` of the best copy (e.g. because its snippets are cleaner)
+#[derive(Debug, Clone, PartialEq)]
+pub struct DedupPreference {
+    pub engine: String,
+    pub margin: f64,
+}
+
+impl DedupPreference {
+    fn prefers(&self, candidate: &SearchResult, kept: &SearchResult) -> bool {
+        candidate.source == self.engine
+            && kept.source != self.engine
+            && kept.score - candidate.score <= self.margin
+    }
+}
+
+// Which title a deduplicated result keeps when its copies were titled differently (Google and
+// DuckDuckGo often title the same page differently)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum DuplicateTitle {
+    // The title matching the most query terms, the first one seen on a tie
+    #[default]
+    QueryTerms,
+    Longest,
+    // The named engine's title, when it returned the page
+    Engine(String),
+}
+
+impl FromStr for DuplicateTitle {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if let Some(engine) = value.strip_prefix("engine:") {
+            return Ok(DuplicateTitle::Engine(engine.trim().to_string()));
+        }
+
+        match value.to_ascii_lowercase().as_str() {
+            "query_terms" => Ok(DuplicateTitle::QueryTerms),
+            "longest" => Ok(DuplicateTitle::Longest),
+            other => Err(format!("unknown duplicate title policy: {}", other)),
+        }
+    }
+}
+
+impl DuplicateTitle {
+    // Whether `candidate`'s title should replace `kept`'s
+    fn prefers(&self, candidate: &SearchResult, kept: &SearchResult, query: &str) -> bool {
+        match self {
+            DuplicateTitle::QueryTerms => {
+                let matches = |title: &str| {
+                    let title = unidecode(&title.to_lowercase());
+                    unidecode(&query.to_lowercase())
+                        .split_whitespace()
+                        .filter(|term| title.contains(term))
+                        .count()
+                };
+                matches(&candidate.title) > matches(&kept.title)
+            }
+            DuplicateTitle::Longest => {
+                candidate.title.trim().chars().count() > kept.title.trim().chars().count()
+            }
+            DuplicateTitle::Engine(engine) => candidate.source == *engine && kept.source != *engine,
+        }
+    }
+}
+
+// What to do with results sharing a title across different domains (republished articles)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TitleCopies {
+    // Treat them as duplicates and keep one
+    #[default]
+    Merge,
+    // Keep them all, but penalize every copy except the authoritative one
+    Penalize,
+}
+
+impl FromStr for TitleCopies {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "merge" => Ok(TitleCopies::Merge),
+            "penalize" => Ok(TitleCopies::Penalize),
+            other => Err(format!("unknown title copies mode: {}", other)),
+        }
+    }
+}
+
+// Clickbait titles and low-value URL paths (tag/category listings), as case-insensitive
+// regular expressions. A result matching either has its score multiplied by `penalty`, once
+// per kind. Deserializes from `{"titles": [regex...], "paths": [regex...], "penalty": 0.7}`.
+// The default patterns come with a penalty of 1, so they only count once one is set.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(from = "LowValuePatternsSpec")]
+pub struct LowValuePatterns {
+    pub titles: Vec<Regex>,
+    pub paths: Vec<Regex>,
+    pub penalty: f64,
+}
+
+impl Default for LowValuePatterns {
+    fn default() -> Self {
+        let titles = [
+            r"\btop \d+\b",
+            r"you won'?t believe",
+            r"\bshocking\b",
+            r"\bthis one (weird )?trick\b",
+            r"\bwhat happens next\b",
+        ];
+        let paths = [r"/tags?/", r"/categor(y|ies)/", r"/author/", r"/page/\d+"];
+
+        Self::new(&titles.map(String::from), &paths.map(String::from), 1.0)
+    }
+}
+
+impl LowValuePatterns {
+    // Invalid patterns are reported and skipped
+    pub fn new(titles: &[String], paths: &[String], penalty: f64) -> Self {
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .filter_map(|pattern| {
+                    RegexBuilder::new(pattern)
+                        .case_insensitive(true)
+                        .build()
+                        .map_err(|e| eprintln!("Invalid low-value pattern {}: {}", pattern, e))
+                        .ok()
+                })
+                .collect()
+        };
+
+        Self {
+            titles: compile(titles),
+            paths: compile(paths),
+            penalty,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct LowValuePatternsSpec {
+    #[serde(default)]
+    titles: Vec<String>,
+    #[serde(default)]
+    paths: Vec<String>,
+    penalty: f64,
+}
+
+impl From<LowValuePatternsSpec> for LowValuePatterns {
+    fn from(spec: LowValuePatternsSpec) -> Self {
+        Self::new(&spec.titles, &spec.paths, spec.penalty)
+    }
+}
+
+// Tunable parts of `ResultScorer::score_result`. Deserializes from a JSON object where
+// missing fields take their defaults.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ScoringConfig {
+    // Added when a query term appears in the result's registrable domain (`react` -> react.dev).
+    // 0, the default, leaves it out.
+    pub domain_match_bonus: f64,
+    // Weight of query-term density in snippet relevance. 0, the default, ignores density.
+    pub snippet_density_weight: f64,
+    // Bonus from the engine's own ordering, `position_weight / rank`: an engine's #1 gets all
+    // of it, its #10 a tenth. 0 ignores engine order.
+    pub position_weight: f64,
+    // Clickbait titles and low-value URL paths
+    pub low_value: LowValuePatterns,
+    // Weigh query words by rarity among the results (`TermWeights`) when scoring coverage, so
+    // matching the one distinctive word counts more than matching the common ones
+    pub idf_coverage: bool,
+    // Added for each of a favicon, a site name and breadcrumbs the result carries. Kept small,
+    // a tiebreaker so close results resolve toward the one that renders best.
+    pub metadata_bonus: f64,
+    // Dated results are multiplied by `age_decay^years` since publication, so a year-old page
+    // keeps 95% of its score by default and a five-year-old one 77%. 1 ignores dates.
+    pub age_decay: f64,
+    // Results on these sites (substring of the link) have their score multiplied by
+    // `blacklist_penalty`
+    pub urls_blacklist: Vec<String>,
+    pub blacklist_penalty: f64,
+    // Results on these sites get `relevant_site_bonus`, and win among same-title copies
+    pub relevant_urls: Vec<String>,
+    pub relevant_site_bonus: f64,
+    // Results mentioning one of these words get `bonus_words_bonus`
+    pub bonus_words: Vec<String>,
+    pub bonus_words_bonus: f64,
+}
+
+impl Default for ScoringConfig {
+    fn default() -> Self {
+        Self {
+            domain_match_bonus: 0.0,
+            snippet_density_weight: 0.0,
+            position_weight: 0.0,
+            low_value: LowValuePatterns::default(),
+            idf_coverage: false,
+            metadata_bonus: 0.02,
+            age_decay: 0.95,
+            urls_blacklist: URLS_BLACKLIST.iter().map(|url| url.to_string()).collect(),
+            blacklist_penalty: 0.25,
+            relevant_urls: RELEVANT_URLS.iter().map(|url| url.to_string()).collect(),
+            relevant_site_bonus: 0.3,
+            bonus_words: BONUS_WORDS.iter().map(|word| word.to_string()).collect(),
+            bonus_words_bonus: 0.5,
+        }
+    }
+}
+
+impl ScoringConfig {
+    // Query word weights for scoring `results`: by rarity with `idf_coverage`, all equal otherwise
+    pub fn term_weights(&self, results: &[SearchResult], query: &str) -> TermWeights {
+        if self.idf_coverage {
+            TermWeights::from_results(results, query)
+        } else {
+            TermWeights::default()
+        }
+    }
+}
+
+// IDF-style weight of each query word, from how many of the scored results mention it:
+// `1 + ln((n + 1) / (df + 1))`, so a word found everywhere weighs 1 and rarer words more.
+// Words without a weight (the default, empty set) weigh 1, which is plain word coverage.
+#[derive(Debug, Clone, Default)]
+pub struct TermWeights(HashMap<String, f64>);
+
+impl TermWeights {
+    pub fn from_results(results: &[SearchResult], query: &str) -> Self {
+        let texts: Vec<String> = results
+            .iter()
+            .map(|result| unidecode(&format!("{} {}", result.title, result.snippet).to_lowercase()))
+            .collect();
+        let corpus_size = texts.len() as f64;
+
+        let weights = unidecode(&query.to_lowercase())
+            .split_whitespace()
+            .map(|word| {
+                let frequency = texts.iter().filter(|text| text.contains(word)).count() as f64;
+                let weight = 1.0 + ((corpus_size + 1.0) / (frequency + 1.0)).ln();
+                (word.to_string(), weight)
+            })
+            .collect();
+
+        Self(weights)
+    }
+
+    fn weight(&self, word: &str) -> f64 {
+        self.0.get(word).copied().unwrap_or(1.0)
+    }
+}
+
+// Results whose host ends with `host` and whose path contains `path` (either may be left out)
+// are of kind `kind`, e.g. `{"host": "youtube.com", "kind": "video"}`
+#[derive(Debug, Clone, Deserialize)]
+pub struct KindRule {
+    pub host: Option<String>,
+    pub path: Option<String>,
+    pub kind: String,
+}
+
+impl KindRule {
+    fn new(host: Option<&str>, path: Option<&str>, kind: &str) -> Self {
+        Self {
+            host: host.map(String::from),
+            path: path.map(String::from),
+            kind: kind.to_string(),
+        }
+    }
+
+    pub fn defaults() -> Vec<KindRule> {
+        vec![
+            KindRule::new(Some("youtube.com"), None, "video"),
+            KindRule::new(Some("youtu.be"), None, "video"),
+            KindRule::new(Some("vimeo.com"), None, "video"),
+            KindRule::new(Some("dailymotion.com"), None, "video"),
+            KindRule::new(Some("github.com"), None, "code"),
+            KindRule::new(Some("gitlab.com"), None, "code"),
+            KindRule::new(Some("bitbucket.org"), None, "code"),
+            KindRule::new(Some("stackoverflow.com"), None, "forum"),
+            KindRule::new(Some("stackexchange.com"), None, "forum"),
+            KindRule::new(Some("reddit.com"), None, "forum"),
+            KindRule::new(Some("docs.rs"), None, "docs"),
+            KindRule::new(Some("developer.mozilla.org"), None, "docs"),
+            KindRule::new(Some("readthedocs.io"), None, "docs"),
+            KindRule::new(None, Some("/docs/"), "docs"),
+            KindRule::new(None, Some("/documentation/"), "docs"),
+            KindRule::new(Some("news.google.com"), None, "news"),
+            KindRule::new(Some("lemonde.fr"), None, "news"),
+            KindRule::new(Some("reuters.com"), None, "news"),
+            KindRule::new(None, Some("/news/"), "news"),
+        ]
+    }
+
+    fn matches(&self, host: &str, path: &str) -> bool {
+        let host_matches = self.host.as_deref().is_none_or(|suffix| {
+            let suffix = suffix.to_ascii_lowercase();
+            host.strip_suffix(suffix.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.ends_with('.'))
+        });
+        let path_matches = self
+            .path
+            .as_deref()
+            .is_none_or(|fragment| path.contains(&fragment.to_ascii_lowercase()));
+
+        host_matches && path_matches && (self.host.is_some() || self.path.is_some())
+    }
+}
+
+// Sites whose results are heavily penalized
+const URLS_BLACKLIST: &[&str] = &[
+    "bfmtv.com",
+    "60millions-mag.com",
+    "bbc.com",
+    "jeuxvideo.com",
+    "linternaute.fr",
+    "lefigaro.fr",
+    "leparisien.fr",
+    "lequipe.fr",
+    "ladepeche.fr",
+    "lepoint.fr",
+    "lejdd.fr",
+    "lesechos.fr",
+    "liberation.fr",
+    "lci.fr",
+    "lemondedutabac.com",
+    "16personalities.com",
+    "freecodecamp.org",
+    "dev.to",
+    "medium.com",
+    "w3schools.com",
+];
+
+// Reputable sites: a scoring bonus, and the authoritative copy among same-title results
+const RELEVANT_URLS: &[&str] = &[
+    "github.com",
+    "docs.rs",
+    "react.dev",
+    "wikipedia.org",
+    "stackoverflow.com",
+    "youtube.com",
+    "reddit.com",
+    "wordpress.com",
+    "gitlab.com",
+    "bitbucket.org",
+    "sourceforge.net",
+    "crates.io",
+    "npmjs.com",
+    "rust-lang.org",
+    "mozilla.org",
+    "developer.mozilla.org",
+    "developer.android.com",
+    "developer.apple.com",
+    "developer.microsoft.com",
+    "developer.chrome.com",
+    "dictionnaire.lerobert.com",
+    "gouv.fr",
+    "openclassrooms.com",
+    "larousse.fr",
+    "cnrtl.fr",
+];
+
+// Words hinting at reference or learning material, in the title, snippet or link
+const BONUS_WORDS: &[&str] = &[
+    "definition",
+    "meaning",
+    "signification",
+    "sens",
+    "tuto",
+    "tutorial",
+    "guide",
+    "cours",
+    "explanation",
+    "explication",
+    "significations",
+    "tutoriel",
+    "guides",
+    "explications",
+    "wikipedia",
+    "wiki",
+    "dictionnaire",
+    "dictionary",
+    "docs",
+    "documentation",
+];
+
+// Parts of a result's score: `(title + snippet + link + https) * penalty` plus the bonuses
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ScoreBreakdown {
+    pub title: f64,
+    pub snippet: f64,
+    pub link: f64,
+    pub https: f64,
+    // Product of the snippet length, blacklist, low-value and age penalties (1 when none applied)
+    pub penalty: f64,
+    pub position: f64,
+    pub relevant_site: f64,
+    pub domain_match: f64,
+    pub exact_match: f64,
+    pub bonus_words: f64,
+    pub metadata: f64,
+    // Rounded to 2 decimals, as `score_result` returns it
+    pub total: f64,
+    // Tags for the penalties and bonuses that applied, for `SearchResult::flags`
+    #[serde(skip)]
+    pub flags: Vec<String>,
+}
+
+// Scores results under one `ScoringConfig`. The search service builds one at startup; the
+// helpers that don't depend on the config are associated functions.
+pub struct ResultScorer {
+    config: ScoringConfig,
+}
 
 impl ResultScorer {
+    pub fn new(config: ScoringConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn config(&self) -> &ScoringConfig {
+        &self.config
+    }
+
     // Calculer le score de pertinence pour un résultat de recherche
-    pub fn score_result(result: &SearchResult, query: &str) -> f64 {
-        let mut score = 0.0;
+    // `weights` ranks query words by rarity, see `TermWeights`.
+    pub fn score_result(&self, result: &SearchResult, query: &str, weights: &TermWeights) -> f64 {
+        self.score_breakdown(result, query, weights).total
+    }
+
+    // `score_result`, component by component
+    pub fn score_breakdown(
+        &self,
+        result: &SearchResult,
+        query: &str,
+        weights: &TermWeights,
+    ) -> ScoreBreakdown {
+        let config = &self.config;
+        let mut breakdown = ScoreBreakdown::default();
         let normalized_query = unidecode(&query.to_lowercase());
         let normalized_title = unidecode(&result.title.to_lowercase());
         let normalized_snippet = unidecode(&result.snippet.to_lowercase());
         let normalized_link = unidecode(&result.link.to_lowercase());
 
-        let urls_blacklist: Vec<&str> = vec![
-            "bfmtv.com",
-            "60millions-mag.com",
-            "bbc.com",
-            "jeuxvideo.com",
-            "linternaute.fr",
-            "lefigaro.fr",
-            "leparisien.fr",
-            "lequipe.fr",
-            "ladepeche.fr",
-            "lepoint.fr",
-            "lejdd.fr",
-            "lesechos.fr",
-            "liberation.fr",
-            "lci.fr",
-            "lemondedutabac.com",
-            "16personalities.com",
-            "freecodecamp.org",
-            "dev.to",
-            "medium.com",
-            "w3schools.com",
-        ];
-
-        let relevant_urls: Vec<&str> = vec![
-            "github.com",
-            "docs.rs",
-            "react.dev",
-            "wikipedia.org",
-            "stackoverflow.com",
-            "youtube.com",
-            "reddit.com",
-            "wordpress.com",
-            "gitlab.com",
-            "bitbucket.org",
-            "sourceforge.net",
-            "crates.io",
-            "npmjs.com",
-            "rust-lang.org",
-            "mozilla.org",
-            "developer.mozilla.org",
-            "developer.android.com",
-            "developer.apple.com",
-            "developer.microsoft.com",
-            "developer.chrome.com",
-            "dictionnaire.lerobert.com",
-            "gouv.fr",
-            "openclassrooms.com",
-            "larousse.fr",
-            "cnrtl.fr",
-        ];
-
-        let bonus_words: Vec<&str> = vec![
-            "definition",
-            "meaning",
-            "signification",
-            "sens",
-            "tuto",
-            "tutorial",
-            "guide",
-            "cours",
-            "explanation",
-            "explication",
-            "significations",
-            "sens",
-            "tutoriel",
-            "guides",
-            "cours",
-            "explications",
-            "wikipedia",
-            "wiki",
-            "dictionnaire",
-            "dictionary",
-            "docs",
-            "documentation",
-        ];
-
         // Score basé sur la pertinence du titre
-        score += Self::calculate_text_relevance(&normalized_title, &normalized_query) * 0.5;
+        breakdown.title =
+            Self::calculate_text_relevance(&normalized_title, &normalized_query, 0.0, weights)
+                * 0.5;
 
         // Score basé sur la pertinence du snippet
-        score += Self::calculate_text_relevance(&normalized_snippet, &normalized_query) * 0.3;
+        breakdown.snippet = Self::calculate_text_relevance(
+            &normalized_snippet,
+            &normalized_query,
+            config.snippet_density_weight,
+            weights,
+        ) * 0.3;
 
         // Score basé sur la pertinence du lien
-        score += Self::calculate_text_relevance(&normalized_link, query) * 0.2;
+        breakdown.link =
+            Self::calculate_text_relevance(&normalized_link, query, 0.0, weights) * 0.2;
 
         // Bonus pour HTTPS ou pour wikipedia
         if normalized_link.starts_with("https") {
-            score += 0.5;
+            breakdown.https = 0.5;
+            breakdown.flags.push("https".to_string());
         }
 
-        // TODO Pénalité pour le contenu ancien
-        // if let Some(date) = result.publish_date {
-        //     let age_in_days = (chrono::Utc::now().date() - date).num_days();
-        //     if age_in_days > 365 {
-        //         score *= 0.9; // Réduire le score de 10% si le contenu a plus d'un an
-        //     }
-        // }
-
         // Penalty for very short or very long snippets
-        if normalized_snippet.len() < 50 || normalized_snippet.len() > 150 {
-            score *= 0.8;
+        // Penalties scale the relevance and HTTPS parts above; the bonuses below are added
+        // on top
+        breakdown.penalty = 1.0;
+        if normalized_snippet.len() < 50 {
+            breakdown.penalty *= 0.8;
+            breakdown.flags.push("short_snippet".to_string());
+        } else if normalized_snippet.len() > 150 {
+            breakdown.penalty *= 0.8;
+            breakdown.flags.push("long_snippet".to_string());
         }
 
         // Penalty for blacklisted URLs
-        if urls_blacklist
+        if config
+            .urls_blacklist
             .iter()
-            .any(|&blacklisted_url| normalized_link.contains(blacklisted_url))
+            .any(|blacklisted_url| normalized_link.contains(blacklisted_url.as_str()))
         {
-            score *= 0.25;
+            breakdown.penalty *= config.blacklist_penalty;
+            breakdown.flags.push("blacklisted".to_string());
+        }
+
+        // Penalty for clickbait titles and listing pages, only flagged when a penalty is set
+        let low_value = &config.low_value;
+        let penalized = low_value.penalty != 1.0;
+        if low_value.titles.iter().any(|pattern| pattern.is_match(&result.title)) {
+            breakdown.penalty *= low_value.penalty;
+            if penalized {
+                breakdown.flags.push("low_value_title".to_string());
+            }
+        }
+        let path = Url::parse(&result.link).map(|url| url.path().to_string()).unwrap_or_default();
+        if low_value.paths.iter().any(|pattern| pattern.is_match(&path)) {
+            breakdown.penalty *= low_value.penalty;
+            if penalized {
+                breakdown.flags.push("low_value_path".to_string());
+            }
+        }
+
+        // Pénalité pour le contenu ancien
+        let age_penalty = Self::age_penalty(result.publish_date, Utc::now(), config.age_decay);
+        breakdown.penalty *= age_penalty;
+        if age_penalty < 1.0 {
+            breakdown.flags.push("old".to_string());
+        }
+
+        // Bonus for ranking high in the engine's own ordering (`rank` is still the engine's here)
+        if result.rank > 0 && config.position_weight != 0.0 {
+            breakdown.position = config.position_weight / result.rank as f64;
+            breakdown.flags.push("position".to_string());
         }
 
         // Bonus for relevant URLs
-        if relevant_urls
+        if config
+            .relevant_urls
             .iter()
-            .any(|&relevant_url| normalized_link.contains(relevant_url))
+            .any(|relevant_url| normalized_link.contains(relevant_url.as_str()))
         {
-            score += 0.3;
+            breakdown.relevant_site = config.relevant_site_bonus;
+            breakdown.flags.push("relevant_domain".to_string());
+        }
+
+        // Bonus for the query naming the registrable domain: "react" -> react.dev, not
+        // example.com/react. Terms must be one of the name's words ("rust" -> rust-lang.org, not
+        // "act" -> react.dev), or the whole query spelled together ("stack overflow").
+        if let Some(name) = Self::domain_name(&normalized_link) {
+            let terms: Vec<&str> = normalized_query
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|term| !term.is_empty())
+                .collect();
+            let mut words = name.split('-');
+            if terms.concat() == name
+                || words.any(|word| word.len() >= 3 && terms.contains(&word))
+            {
+                breakdown.domain_match = config.domain_match_bonus;
+            }
+        }
+        if breakdown.domain_match != 0.0 {
+            breakdown.flags.push("domain_match".to_string());
         }
 
         // Bonus for exact match in title
         if normalized_title == normalized_query {
-            score += 0.5;
+            breakdown.exact_match += 0.5;
         }
 
         // Bonus for exact match in snippet
         if normalized_snippet == normalized_query {
-            score += 0.75;
+            breakdown.exact_match += 0.75;
+        }
+        if breakdown.exact_match > 0.0 {
+            breakdown.flags.push("exact_match".to_string());
         }
 
         // Bonus for choosen words on the title, snippet or link
-        if bonus_words.iter().any(|&bonus_word| {
-            normalized_title.contains(bonus_word)
-                || normalized_snippet.contains(bonus_word)
-                || normalized_link.contains(bonus_word)
+        if config.bonus_words.iter().any(|bonus_word| {
+            normalized_title.contains(bonus_word.as_str())
+                || normalized_snippet.contains(bonus_word.as_str())
+                || normalized_link.contains(bonus_word.as_str())
         }) {
-            score += 0.5;
+            breakdown.bonus_words = config.bonus_words_bonus;
+            breakdown.flags.push("bonus_word".to_string());
+        }
+
+        // Tiebreaker for results the UI can show with icon, site name and path
+        let metadata_fields = [
+            result.favicon_url.is_some(),
+            result.site_name.is_some(),
+            !result.breadcrumbs.is_empty(),
+        ];
+        let metadata_count = metadata_fields.iter().filter(|&&present| present).count();
+        breakdown.metadata = config.metadata_bonus * metadata_count as f64;
+        if breakdown.metadata != 0.0 {
+            breakdown.flags.push("metadata".to_string());
         }
 
+        let score = (breakdown.title + breakdown.snippet + breakdown.link + breakdown.https)
+            * breakdown.penalty
+            + breakdown.position
+            + breakdown.relevant_site
+            + breakdown.domain_match
+            + breakdown.exact_match
+            + breakdown.bonus_words
+            + breakdown.metadata;
         // limit float to 2 decimal places
+        breakdown.total = (score * 100.0).round() / 100.0;
+        breakdown
+    }
+
+    // Blend the heuristic score with the engine's priority. This runs on the rounded output
+    // of `score_result` (there is no separate normalization step), so a weight scales the
+    // whole heuristic score and the bonus shifts it; engines without an entry are neutral.
+    pub fn apply_engine_weight(
+        result: &SearchResult,
+        weights: &HashMap<String, EngineWeight>,
+    ) -> f64 {
+        let engine = weights.get(&result.source).copied().unwrap_or_default();
+        let score = result.score * engine.weight + engine.priority_bonus;
+
         (score * 100.0).round() / 100.0
     }
 
-    // Calculer la pertinence du texte en utilisant le comptage des termes
-    fn calculate_text_relevance(text: &str, query: &str) -> f64 {
+    // Calculer la pertinence du texte en utilisant le comptage des termes.
+    // `density_weight` adds how often query terms occur relative to the text's length.
+    fn calculate_text_relevance(
+        text: &str,
+        query: &str,
+        density_weight: f64,
+        weights: &TermWeights,
+    ) -> f64 {
         // Levenshtein distance for fuzzy matching
-        let levenshtein_score = normalized_levenshtein(&text, &query);
+        let levenshtein_score = normalized_levenshtein(text, query);
 
         // Exact match bonus
-        let contains_exact = text.contains(&query) as i32 as f64;
+        let contains_exact = text.contains(query) as i32 as f64;
 
-        // Word match ratio
+        // Word match ratio, rarer words counting for more
         let query_words: Vec<&str> = query.split_whitespace().collect();
-        let matching_words = query_words
+        let matching_words: f64 = query_words
             .iter()
             .filter(|word| text.contains(*word))
-            .count() as f64;
-        let word_ratio = matching_words / query_words.len() as f64;
+            .map(|word| weights.weight(word))
+            .sum();
+        let total_words: f64 = query_words.iter().map(|word| weights.weight(word)).sum();
+        let word_ratio = matching_words / total_words;
 
         // Combine scores with weights
-        0.3 * levenshtein_score + 0.4 * contains_exact + 0.3 * word_ratio
+        0.3 * levenshtein_score
+            + 0.4 * contains_exact
+            + 0.3 * word_ratio
+            + density_weight * Self::term_density(text, &query_words)
+    }
+
+    // Share of the text's words that are query terms, scaled so a term every fifth word counts
+    // as fully dense: 3 mentions in 50 words beat 1 in 500. 0 for empty text.
+    fn term_density(text: &str, query_words: &[&str]) -> f64 {
+        let words: Vec<&str> = text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect();
+        if words.is_empty() {
+            return 0.0;
+        }
+
+        let occurrences = words.iter().filter(|word| query_words.contains(word)).count();
+        (occurrences as f64 / words.len() as f64 * 5.0).min(1.0)
     }
 
-    // Remove duplicate results based on URL similarity
-    pub fn remove_duplicates(results: Vec<SearchResult>) -> Vec<SearchResult> {
-        let mut seen: Vec<SearchResult> = Vec::new();
-        let mut unique_results = Vec::new();
+    // Hard filter for results with empty or truncated snippets, which are usually parsing
+    // failures. Homepages are kept regardless since their snippets are naturally short.
+    pub fn has_min_snippet(result: &SearchResult, min_length: usize) -> bool {
+        if result.snippet.trim().chars().count() >= min_length {
+            return true;
+        }
 
-        for result in results {
-            let is_duplicate = seen
+        Url::parse(&result.link)
+            .map(|url| url.path() == "/" && url.query().is_none())
+            .unwrap_or(false)
+    }
+
+    // Smooth decay with age, `decay^years`. Undated results and dates in the future (clock skew,
+    // misparsed dates) are left alone.
+    pub fn age_penalty(publish_date: Option<DateTime<Utc>>, now: DateTime<Utc>, decay: f64) -> f64 {
+        let Some(date) = publish_date else {
+            return 1.0;
+        };
+        let years = (now - date).num_seconds().max(0) as f64 / (365.25 * 86_400.0);
+
+        decay.powf(years)
+    }
+
+    // Hard filter for empty or near-empty titles, which come from malformed result markup
+    pub fn has_min_title(result: &SearchResult, min_length: usize) -> bool {
+        result.title.trim().chars().count() >= min_length
+    }
+
+    // Canonical URL behind an AMP cache URL, e.g. `https://www.google.com/amp/s/example.com/a`
+    // or `https://example-com.cdn.ampproject.org/c/s/example.com/a` -> `https://example.com/a`.
+    // `None` when the URL isn't AMP or the origin can't be recovered from it.
+    pub fn de_amp(link: &str) -> Option<String> {
+        let url = Url::parse(link).ok()?;
+        let host = url.host_str()?;
+        let path = url.path();
+
+        let wrapped = if host.ends_with(".cdn.ampproject.org") {
+            // `/c/` pages, `/v/` viewer, `/i/` images, `/r/` resources
+            ["/c/", "/v/", "/i/", "/r/"]
                 .iter()
-                .any(|seen_link| Self::is_duplicate(&result, seen_link));
+                .find_map(|prefix| path.strip_prefix(prefix))?
+        } else if host.starts_with("www.google.") || host.starts_with("google.") {
+            path.strip_prefix("/amp/")?
+        } else {
+            return None;
+        };
+
+        // A leading `s/` marks an https origin
+        let (scheme, rest) = match wrapped.strip_prefix("s/") {
+            Some(rest) => ("https", rest),
+            None => ("http", wrapped),
+        };
+
+        let mut canonical = Url::parse(&format!("{}://{}", scheme, rest)).ok()?;
+        if !canonical.host_str()?.contains('.') {
+            return None;
+        }
+        canonical.set_query(url.query());
+
+        Some(canonical.to_string())
+    }
+
+    // `https://` version of an `http://` link on one of `domains` (subdomains included);
+    // `None` for other links, which may be on HTTP-only sites
+    pub fn upgrade_to_https(link: &str, domains: &[String]) -> Option<String> {
+        let mut url = Url::parse(link).ok()?;
+        if url.scheme() != "http" {
+            return None;
+        }
+
+        let host = url.host_str()?.to_ascii_lowercase();
+        let listed = domains.iter().any(|domain| {
+            host.strip_suffix(domain.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.ends_with('.'))
+        });
+        if !listed {
+            return None;
+        }
+        url.set_scheme("https").ok()?;
+
+        Some(url.to_string())
+    }
 
-            if !is_duplicate {
-                seen.push(result.clone());
-                unique_results.push(result);
+    // Kind of the first rule matching the link, for badges in the UI
+    pub fn classify_kind(link: &str, rules: &[KindRule]) -> Option<String> {
+        let url = Url::parse(link).ok()?;
+        let host = url.host_str()?.to_ascii_lowercase();
+        let path = url.path().to_ascii_lowercase();
+
+        rules
+            .iter()
+            .find(|rule| rule.matches(&host, &path))
+            .map(|rule| rule.kind.clone())
+    }
+
+    // Like `interleave`, but each round (every engine's n-th result) is ordered by score
+    pub fn interleave_by_rank(lists: Vec<Vec<SearchResult>>) -> Vec<SearchResult> {
+        let mut iterators: Vec<_> = lists.into_iter().map(Vec::into_iter).collect();
+        let mut merged = Vec::new();
+
+        loop {
+            let mut round: Vec<SearchResult> =
+                iterators.iter_mut().filter_map(Iterator::next).collect();
+            if round.is_empty() {
+                return merged;
+            }
+
+            round.sort();
+            merged.extend(round);
+        }
+    }
+
+    // Trim per-engine lists so they hold at most `max` results in total, each engine keeping
+    // its own top results in proportion to how many it returned
+    pub fn cap_candidates(lists: &mut [Vec<SearchResult>], max: usize) {
+        let total: usize = lists.iter().map(Vec::len).sum();
+        if total <= max {
+            return;
+        }
+
+        let mut quotas: Vec<usize> = lists.iter().map(|list| list.len() * max / total).collect();
+        // Rounding down leaves a few slots, handed out in engine order
+        let mut spare = max - quotas.iter().sum::<usize>();
+        for (quota, list) in quotas.iter_mut().zip(lists.iter()) {
+            if spare == 0 {
+                break;
+            }
+            if *quota < list.len() {
+                *quota += 1;
+                spare -= 1;
             }
         }
 
-        unique_results
+        for (list, quota) in lists.iter_mut().zip(quotas) {
+            list.truncate(quota);
+        }
     }
 
-    // Check if two URLs point to the same content
-    fn is_duplicate(result1: &SearchResult, result2: &SearchResult) -> bool {
-        let url1 = &result1.link;
-        let url2 = &result2.link;
-        let normalize_url = |url: &str| {
-            if let Ok(parsed_url) = Url::parse(url) {
-                let mut normalized = format!(
-                    "{}{}",
-                    parsed_url.host_str().unwrap_or(""),
-                    parsed_url.path()
-                );
-                normalized = normalized
-                    .trim_end_matches('/')
-                    .replace("www.", "")
-                    .to_lowercase();
-                normalized
-            } else {
-                url.trim_end_matches('/').replace("www.", "").to_lowercase()
+    // Make sure every engine with results has at least `floor` of them in the first `window`
+    // positions, so a fast or high-scoring engine can't crowd the others off the first page.
+    // Slots are taken from the lowest-ranked results of engines above their floor.
+    pub fn apply_engine_floor(
+        mut results: Vec<SearchResult>,
+        floor: usize,
+        window: usize,
+    ) -> Vec<SearchResult> {
+        let window = window.min(results.len());
+        let mut engines: Vec<String> = Vec::new();
+        for result in &results {
+            if !engines.contains(&result.source) {
+                engines.push(result.source.clone());
             }
+        }
+
+        let count_in_window = |results: &[SearchResult], engine: &str| {
+            results[..window].iter().filter(|r| r.source == engine).count()
         };
 
-        let url1_norm = normalize_url(url1);
-        let url2_norm = normalize_url(url2);
+        for engine in &engines {
+            while count_in_window(&results, engine) < floor {
+                let Some(candidate) = results[window..]
+                    .iter()
+                    .position(|r| &r.source == engine)
+                    .map(|index| index + window)
+                else {
+                    break;
+                };
+                let Some(evicted) = (0..window)
+                    .rev()
+                    .find(|&index| count_in_window(&results, &results[index].source) > floor)
+                else {
+                    break;
+                };
+
+                // The promoted result closes the window, the evicted one opens the rest
+                let promoted = results.remove(candidate);
+                let demoted = results.remove(evicted);
+                results.insert(window - 1, promoted);
+                results.insert(window, demoted);
+            }
+        }
+
+        results
+    }
+
+    // Multiply the score of republished copies (same title, another domain) by `penalty`.
+    // The copy on a `relevant_urls` site is authoritative, otherwise the best-scored one is.
+    pub fn penalize_title_copies(
+        results: &mut [SearchResult],
+        penalty: f64,
+        relevant_urls: &[String],
+    ) {
+        let mut groups: HashMap<String, Vec<usize>> = HashMap::new();
+        for (index, result) in results.iter().enumerate() {
+            let title = unidecode(&result.title.trim().to_lowercase());
+            if !title.is_empty() {
+                groups.entry(title).or_default().push(index);
+            }
+        }
+
+        for indices in groups.into_values().filter(|indices| indices.len() > 1) {
+            let is_relevant = |index: &usize| {
+                let link = results[*index].link.to_lowercase();
+                relevant_urls.iter().any(|relevant| link.contains(relevant.as_str()))
+            };
+            let best = |a: &&usize, b: &&usize| results[**a].score.total_cmp(&results[**b].score);
+            let authoritative = indices
+                .iter()
+                .filter(|index| is_relevant(index))
+                .max_by(best)
+                .or_else(|| indices.iter().max_by(best))
+                .copied();
+            let Some(authoritative) = authoritative else {
+                continue;
+            };
+
+            let domain = Self::domain(&results[authoritative].link);
+            for index in indices {
+                // Same-site copies are left to dedup
+                if Self::domain(&results[index].link) != domain {
+                    results[index].score = (results[index].score * penalty * 100.0).round() / 100.0;
+                    results[index].flags.push("title_copy".to_string());
+                }
+            }
+        }
+    }
+
+    fn domain(link: &str) -> String {
+        Url::parse(link)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.trim_start_matches("www.").to_string()))
+            .unwrap_or_default()
+    }
+
+    // Name part of the registrable domain, per the public suffix list: `react` for
+    // `https://fr.react.dev/learn`, `bbc` for `https://news.bbc.co.uk`, `tokio-rs` for
+    // `https://tokio-rs.github.io/axum`
+    pub fn domain_name(link: &str) -> Option<String> {
+        let url = Url::parse(link).ok()?;
+        let domain = PUBLIC_SUFFIXES.domain(url.domain()?.as_bytes())?;
+        let name = domain.as_bytes().strip_suffix(domain.suffix().as_bytes())?;
+
+        String::from_utf8(name.strip_suffix(b".")?.to_vec()).ok()
+    }
+
+    // Merge per-engine lists without scoring: first result of each engine, then the second of
+    // each, and so on, keeping every engine's native order
+    pub fn interleave(lists: Vec<Vec<SearchResult>>) -> Vec<SearchResult> {
+        let total = lists.iter().map(Vec::len).sum();
+        let mut iterators: Vec<_> = lists.into_iter().map(Vec::into_iter).collect();
+        let mut merged = Vec::with_capacity(total);
 
-        url1_norm == url2_norm
-            || normalized_levenshtein(&url1_norm, &url2_norm) > 0.9
-            || result1.title == result2.title
+        while merged.len() < total {
+            for iterator in &mut iterators {
+                merged.extend(iterator.next());
+            }
+        }
+
+        merged
+    }
+
+    // Remove duplicate results based on URL similarity, across engines or within each engine
+    // depending on `scope`; same-title copies on other domains survive with
+    // `TitleCopies::Penalize`. Results are expected best-first, so the first copy is kept unless
+    // `preference` favors a later copy from its preferred engine.
+    pub fn remove_duplicates(
+        results: Vec<SearchResult>,
+        scope: DedupScope,
+        fragment_sensitive: bool,
+        title_copies: TitleCopies,
+        preference: Option<&DedupPreference>,
+        title_choice: &DuplicateTitle,
+        query: &str,
+    ) -> Vec<SearchResult> {
+        let mut unique_results: Vec<SearchResult> = Vec::new();
+        let mut keys: Vec<DedupKey> = Vec::new();
+        let mut index = DedupIndex::default();
+
+        for mut result in results {
+            if result.sources.is_empty() {
+                result.sources.push(result.source.clone());
+            }
+            // Only kept results that could match are compared, still in order, so the first
+            // duplicate found is the same as comparing against every kept result
+            let key = DedupKey::new(&result.link);
+            let duplicate_of = index.candidates(&result, &key).into_iter().find(|&position| {
+                let kept = &unique_results[position];
+                (scope == DedupScope::Global || kept.source == result.source)
+                    && Self::is_duplicate(
+                        &result,
+                        &key,
+                        kept,
+                        &keys[position],
+                        fragment_sensitive,
+                        title_copies,
+                    )
+            });
+
+            match duplicate_of {
+                Some(index_of) => {
+                    // The title is picked separately from which copy is kept
+                    let kept = &mut unique_results[index_of];
+                    let better_title = title_choice.prefers(&result, kept, query);
+                    if preference.is_some_and(|p| p.prefers(&result, kept)) {
+                        if !better_title {
+                            result.title = std::mem::take(&mut kept.title);
+                        }
+                        let merged = std::mem::take(&mut kept.sources);
+                        *kept = result;
+                        Self::add_sources(kept, merged);
+                    } else {
+                        if better_title {
+                            kept.title = result.title;
+                        }
+                        Self::add_sources(kept, result.sources);
+                    }
+
+                    // The kept copy may have a new link or title now
+                    keys[index_of] = DedupKey::new(&kept.link);
+                    index.insert(index_of, kept, &keys[index_of]);
+                }
+                None => {
+                    index.insert(unique_results.len(), &result, &key);
+                    unique_results.push(result);
+                    keys.push(key);
+                }
+            }
+        }
+
+        unique_results
+    }
+
+    // Record the engines behind a merged duplicate, once each
+    fn add_sources(kept: &mut SearchResult, sources: Vec<String>) {
+        for source in sources {
+            if !kept.sources.contains(&source) {
+                kept.sources.push(source);
+            }
+        }
+    }
+
+    // Check if two URLs point to the same content. With `fragment_sensitive`, links to
+    // different fragments of one page are sections with their own content, never duplicates.
+    // The fuzzy URL comparison is by far the slowest, so it goes last.
+    fn is_duplicate(
+        result1: &SearchResult,
+        key1: &DedupKey,
+        result2: &SearchResult,
+        key2: &DedupKey,
+        fragment_sensitive: bool,
+        title_copies: TitleCopies,
+    ) -> bool {
+        if fragment_sensitive && key1.url == key2.url && key1.fragment != key2.fragment {
+            return false;
+        }
+
+        key1.url == key2.url
+            || (result1.title == result2.title
+                && (title_copies == TitleCopies::Merge || key1.domain == key2.domain))
             || result1.snippet == result2.snippet
+            || normalized_levenshtein(&key1.url, &key2.url) > 0.9
+    }
+}
+
+// What `is_duplicate` compares of a link, computed once per result instead of once per pair
+struct DedupKey {
+    // Host and path, lowercased, without `www.` or a trailing slash
+    url: String,
+    // `url` in characters
+    length: usize,
+    fragment: Option<String>,
+    domain: String,
+}
+
+impl DedupKey {
+    fn new(link: &str) -> Self {
+        let parsed = Url::parse(link).ok();
+        let url = match &parsed {
+            Some(parsed) => format!("{}{}", parsed.host_str().unwrap_or(""), parsed.path())
+                .trim_end_matches('/')
+                .replace("www.", "")
+                .to_lowercase(),
+            None => link.trim_end_matches('/').replace("www.", "").to_lowercase(),
+        };
+
+        Self {
+            length: url.chars().count(),
+            url,
+            fragment: parsed.as_ref().and_then(|parsed| parsed.fragment().map(str::to_string)),
+            domain: ResultScorer::domain(link),
+        }
+    }
+}
+
+// Kept results by what a duplicate would share with them. Entries stay when a kept result
+// changes, so lookups may return stale candidates, which `is_duplicate` then rejects.
+#[derive(Default)]
+struct DedupIndex {
+    by_url: HashMap<String, Vec<usize>>,
+    by_title: HashMap<String, Vec<usize>>,
+    by_snippet: HashMap<String, Vec<usize>>,
+    by_length: BTreeMap<usize, Vec<usize>>,
+}
+
+impl DedupIndex {
+    fn insert(&mut self, position: usize, result: &SearchResult, key: &DedupKey) {
+        self.by_url.entry(key.url.clone()).or_default().push(position);
+        self.by_title.entry(result.title.clone()).or_default().push(position);
+        self.by_snippet.entry(result.snippet.clone()).or_default().push(position);
+        self.by_length.entry(key.length).or_default().push(position);
+    }
+
+    // Positions of the kept results `result` could duplicate, in order. URLs more than 10%
+    // apart in length can't pass the fuzzy comparison: the edit distance is at least the
+    // length difference.
+    fn candidates(&self, result: &SearchResult, key: &DedupKey) -> Vec<usize> {
+        let lengths = key.length * 9 / 10..=(key.length * 10).div_ceil(9);
+        let mut positions: Vec<usize> = [
+            self.by_url.get(&key.url),
+            self.by_title.get(&result.title),
+            self.by_snippet.get(&result.snippet),
+        ]
+        .into_iter()
+        .flatten()
+        .chain(self.by_length.range(lengths).map(|(_, positions)| positions))
+        .flatten()
+        .copied()
+        .collect();
+        positions.sort_unstable();
+        positions.dedup();
+        positions
     }
 }
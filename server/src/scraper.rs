@@ -1,10 +1,21 @@
+use crate::browser::HeadlessBrowser;
 use crate::error::SearchError;
+use crate::query::{encode_query, DateFilter, OperatorKind};
 use async_trait::async_trait;
+use chrono::{DateTime, Days, Months, NaiveDate, NaiveTime, TimeDelta, Utc};
+use encoding_rs::{Encoding, UTF_8};
 use rand::seq::SliceRandom;
 use scraper::{Html, Selector};
+use reqwest::header::{
+    HeaderMap, HeaderValue, ACCEPT, ACCEPT_LANGUAGE, DNT, REFERER, UPGRADE_INSECURE_REQUESTS,
+};
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::Duration;
 use serde_json::Value;
+use url::Url;
 
 #[derive(Debug, Serialize, Clone, Deserialize)]
 pub struct Breadcrumb {
@@ -12,16 +23,124 @@ pub struct Breadcrumb {
     pub url: Option<String>,
 }
 
+impl Breadcrumb {
+    // Breadcrumbs as the engines render them, e.g. `example.com › docs › intro`
+    pub fn display(breadcrumbs: &[Breadcrumb]) -> Option<String> {
+        let parts: Vec<&str> = breadcrumbs
+            .iter()
+            .map(|breadcrumb| breadcrumb.text.as_str())
+            .filter(|text| !text.is_empty())
+            .collect();
+
+        (!parts.is_empty()).then(|| parts.join(" › "))
+    }
+
+    // The reverse of `display`: crumbs from a rendered `example.com › docs › intro` trail,
+    // each linking to the path up to it
+    pub fn parse_trail(trail: &str) -> Vec<Breadcrumb> {
+        let mut url_accumulator = String::new();
+        trail
+            .split('›')
+            .map(str::trim)
+            .filter(|part| !part.is_empty())
+            .map(|part| {
+                if !url_accumulator.is_empty() {
+                    url_accumulator.push('/');
+                }
+                url_accumulator.push_str(part);
+                Breadcrumb {
+                    text: part.to_string(),
+                    url: Some(url_accumulator.clone()),
+                }
+            })
+            .collect()
+    }
+}
+
+// Sub-link shown under prominent results (e.g. "Documentation", "Downloads")
+#[derive(Debug, Serialize, Clone, Deserialize)]
+pub struct Sitelink {
+    pub title: String,
+    pub link: String,
+}
+
+// Google's "position zero" answer box, shown above the organic results
 #[derive(Debug, Serialize, Clone, Deserialize)]
+pub struct FeaturedSnippet {
+    pub text: String,
+    pub link: String,
+    // Title of the page the answer is quoted from
+    pub source: String,
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize, Default)]
+#[serde(default)]
 pub struct SearchResult {
     pub title: String,
     pub link: String,
     pub snippet: String,
     pub source: String,
+    // Every engine that found this result once duplicates are merged, `source` (the copy
+    // kept) first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<String>,
     pub score: f64,
+    // `score_result`'s heuristic score, before engine weights and penalties adjusted it into
+    // `score`; only filled in with `EXPOSE_RAW_SCORE`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_score: Option<f64>,
+    // Why scoring boosted or penalized it, one tag per adjustment that applied: `https`,
+    // `short_snippet`/`long_snippet`, `blacklisted`, `low_value_title`/`low_value_path`, `old`,
+    // `position`, `relevant_domain`, `domain_match`, `exact_match`, `bonus_word`, `metadata`,
+    // `title_copy`.
+    // Only sent with `explain=true`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub flags: Vec<String>,
     pub favicon_url: Option<String>,
     pub site_name: Option<String>,
     pub breadcrumbs: Vec<Breadcrumb>,
+    // `breadcrumbs` joined for display, so clients don't have to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sitelinks: Vec<Sitelink>,
+    // Rich-snippet star rating (out of 5) and price, for product/review results
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rating: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price: Option<String>,
+    // 1-based position in the final ranking; the position within its engine's page until
+    // `SearchService::search` ranks it
+    #[serde(default)]
+    pub rank: usize,
+    // Badge such as `video`, `code` or `forum`, from `ResultScorer::classify_kind`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    // Age as the engine displays it ("3 days ago", "il y a 3 jours"), verbatim
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub age_text: Option<String>,
+    // `age_text` resolved to a date, when it can be parsed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub publish_date: Option<DateTime<Utc>>,
+}
+
+// Everything an engine extracted from one results page
+#[derive(Debug, Clone, Default)]
+pub struct SearchPage {
+    pub results: Vec<SearchResult>,
+    // "People also ask" questions, when the engine shows them (first page only)
+    pub people_also_ask: Vec<String>,
+    // Answer box, when the engine shows one (first page only)
+    pub featured_snippet: Option<FeaturedSnippet>,
+}
+
+impl From<Vec<SearchResult>> for SearchPage {
+    fn from(results: Vec<SearchResult>) -> Self {
+        Self {
+            results,
+            ..Default::default()
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Clone, Deserialize)]
@@ -29,7 +148,12 @@ pub struct QuickAnswer {
     pub answer_type: String,
     #[serde(flatten)]
     pub data: Value,
+    // Name of the engine that produced the answer (`SearchEngine::name`)
     pub source: String,
+    // How sure the engine is of the answer, from 0 to 1; the best one wins when several
+    // engines answer
+    #[serde(default)]
+    pub confidence: f64,
 }
 
 #[derive(Debug, Serialize, Clone, Deserialize)]
@@ -39,14 +163,20 @@ pub struct Definition {
 }
 
 impl QuickAnswer {
-    pub fn new_definition(term: String, definition: String, source: Option<String>) -> Self {
+    pub fn new_definition(
+        term: String,
+        definition: String,
+        source: &str,
+        confidence: f64,
+    ) -> Self {
         Self {
             answer_type: "definition".to_string(),
             data: serde_json::to_value(Definition {
                 term,
                 definition,
             }).unwrap(),
-            source: source.unwrap_or_default(),
+            source: source.to_string(),
+            confidence,
         }
     }
 }
@@ -59,18 +189,25 @@ impl PartialOrd for SearchResult {
 
 impl PartialEq for SearchResult {
     fn eq(&self, other: &Self) -> bool {
-        self.score == other.score
+        self.cmp(other) == std::cmp::Ordering::Equal
     }
 }
 
 impl Eq for SearchResult {}
 
+impl SearchResult {
+    // Scores are reported with 2 decimals; compare on that same precision so float noise
+    // beyond it can't reorder otherwise-tied results between runs
+    fn rounded_score(&self) -> i64 {
+        (self.score * 100.0).round() as i64
+    }
+}
+
 impl Ord for SearchResult {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         other
-            .score
-            .partial_cmp(&self.score)
-            .unwrap()
+            .rounded_score()
+            .cmp(&self.rounded_score())
             .then_with(|| self.title.cmp(&other.title))
             .then_with(|| self.link.cmp(&other.link))
     }
@@ -105,47 +242,217 @@ impl Ord for SearchResult {
     }
 }
 
-struct SearchResultWrapper(SearchResult);
-
-impl PartialOrd for SearchResultWrapper {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
-    }
+// Service used to build favicon URLs for results that don't carry their own
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FaviconProvider {
+    #[default]
+    Google,
+    DuckDuckGo,
+    // No favicons at all, so clients never hit a third-party icon service
+    None,
 }
 
-impl Ord for SearchResultWrapper {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.0.score.partial_cmp(&other.0.score).unwrap()
+impl FaviconProvider {
+    pub fn favicon_url(&self, domain: &str) -> Option<String> {
+        if domain.is_empty() {
+            return None;
+        }
+
+        match self {
+            FaviconProvider::Google => Some(format!(
+                "https://www.google.com/s2/favicons?domain={}",
+                domain
+            )),
+            FaviconProvider::DuckDuckGo => {
+                Some(format!("https://icons.duckduckgo.com/ip3/{}.ico", domain))
+            }
+            FaviconProvider::None => None,
+        }
     }
 }
 
-impl PartialEq for SearchResultWrapper {
-    fn eq(&self, other: &Self) -> bool {
-        self.0.score == other.0.score
+impl FromStr for FaviconProvider {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "google" => Ok(FaviconProvider::Google),
+            "duckduckgo" => Ok(FaviconProvider::DuckDuckGo),
+            "none" => Ok(FaviconProvider::None),
+            other => Err(format!("unknown favicon provider: {}", other)),
+        }
     }
 }
 
-impl Eq for SearchResultWrapper {}
-
 const USER_AGENTS: &[&str] = &[
     "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36",
     "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36",
     "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36 Edg/120.0.0.0",
 ];
 
+// First number in a rating text ("4,5", "Rated 4.5 out of 5"), if it's a plausible 0-5 rating
+fn parse_rating(text: &str) -> Option<f64> {
+    let start = text.find(|c: char| c.is_ascii_digit())?;
+    let number: String = text[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.' || *c == ',')
+        .collect();
+
+    number
+        .trim_end_matches(['.', ','])
+        .replace(',', ".")
+        .parse::<f64>()
+        .ok()
+        .filter(|rating| (0.0..=5.0).contains(rating))
+}
+
+// Short text with a currency symbol or code and a digit, e.g. "29,99 €" or "US$15.00"
+fn looks_like_price(text: &str) -> bool {
+    const CURRENCIES: &[&str] = &["€", "$", "£", "¥", "EUR", "USD", "GBP"];
+
+    text.len() <= 32
+        && text.chars().any(|c| c.is_ascii_digit())
+        && CURRENCIES.iter().any(|currency| text.contains(currency))
+}
+
+// Read an HTML body in its declared charset: the `Content-Type` header first, then a BOM or
+// `<meta charset>` in the page itself, falling back to lossy UTF-8
+async fn read_html(response: reqwest::Response) -> Result<String, SearchError> {
+    let header_charset = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|content_type| charset_param(content_type, "charset="))
+        .and_then(|label| Encoding::for_label(label.as_bytes()));
+    let bytes = response.bytes().await?;
+
+    let encoding = header_charset
+        .or_else(|| Encoding::for_bom(&bytes).map(|(encoding, _)| encoding))
+        .or_else(|| meta_charset(&bytes))
+        .unwrap_or(UTF_8);
+    let (html, _, _) = encoding.decode(&bytes);
+
+    Ok(html.into_owned())
+}
+
+// Endpoint from `var` (e.g. `GOOGLE_BASE_URL`) when set, for mock servers and regional
+// mirrors. Engines are built once at startup, so leaking it gives the `&'static str`
+// `base_url()` returns. A value that isn't an absolute URL is ignored, so engines can rely
+// on `base_url()` parsing.
+pub(crate) fn base_url_override(var: &str, default: &'static str) -> &'static str {
+    std::env::var(var)
+        .ok()
+        .map(|value| value.trim().trim_end_matches('/').to_string())
+        .filter(|value| !value.is_empty())
+        .filter(|value| match Url::parse(value) {
+            Ok(_) => true,
+            Err(e) => {
+                eprintln!("Ignoring invalid {} {:?}, using {}: {}", var, value, default, e);
+                false
+            }
+        })
+        .map(|value| &*Box::leak(value.into_boxed_str()))
+        .unwrap_or(default)
+}
+
+// Charset declared by `<meta charset="...">` or `<meta http-equiv="Content-Type"
+// content="text/html; charset=...">` in the start of the page
+fn meta_charset(bytes: &[u8]) -> Option<&'static Encoding> {
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(2048)]).to_ascii_lowercase();
+
+    head.match_indices("<meta").find_map(|(start, _)| {
+        let tag = &head[start..];
+        let tag = &tag[..tag.find('>').unwrap_or(tag.len())];
+        let label = charset_param(tag, "charset=")?;
+        Encoding::for_label(label.as_bytes())
+    })
+}
+
+// Value following `key` in a header or tag, without quotes
+fn charset_param<'a>(text: &'a str, key: &str) -> Option<&'a str> {
+    let start = text.to_ascii_lowercase().find(key)? + key.len();
+    let value = text[start..].trim_start_matches(['"', '\'', ' ']);
+    let end = value
+        .find(['"', '\'', ';', ' ', '/', '>'])
+        .unwrap_or(value.len());
+
+    (end > 0).then(|| &value[..end])
+}
+
+// Decode entities that survive HTML parsing (e.g. double-escaped `&amp;#39;` in attributes)
+fn decode_html_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest.find(';').map(|end| (&rest[1..end], end));
+        let replacement = entity.and_then(|(name, _)| match name {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => name
+                .strip_prefix("#x")
+                .or_else(|| name.strip_prefix("#X"))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| name.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                .and_then(char::from_u32),
+        });
+
+        match (replacement, entity) {
+            (Some(c), Some((_, end))) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+
+    decoded
+}
+
 #[async_trait]
 pub trait SearchEngine: Send + Sync {
     fn name(&self) -> &'static str;
     fn base_url(&self) -> &'static str;
 
+    // Operators passed through to this engine; the others are stripped from the query
+    fn supported_operators(&self) -> &[OperatorKind] {
+        OperatorKind::ALL
+    }
+
+    // Whether `a OR b` in the query means either term, for synonym expansion
+    fn supports_or(&self) -> bool {
+        false
+    }
+
     async fn search(
         &self,
         query: &str,
         page: u32,
         date_range: Option<&str>,
+        date_filter: &DateFilter,
         region: Option<&str>,
         language: Option<&str>,
-    ) -> Result<Vec<SearchResult>, SearchError>;
+    ) -> Result<SearchPage, SearchError>;
+
+    // Extra headers sent with every request to this engine, on top of (and overriding) the
+    // shared `Accept` ones
+    fn default_headers(&self) -> HeaderMap {
+        HeaderMap::new()
+    }
 
     async fn fetch_html(&self, url: &str) -> Result<String, SearchError> {
         let client = reqwest::Client::builder()
@@ -154,52 +461,310 @@ pub trait SearchEngine: Send + Sync {
             .timeout(Duration::from_secs(30))
             .build()?;
 
-        Ok(client
+        let response = client
             .get(url)
-            .header("Accept", "text/html")
-            .header("Accept-Language", "fr-FR,fr;q=0.9")
+            .headers(request_headers(self.default_headers()))
             .send()
-            .await?
-            .text()
-            .await?)
+            .await?;
+
+        read_html(response).await
     }
 
     fn parse_results(&self, html: &str) -> Vec<SearchResult>;
 
-    async fn quick_answer(&self, query: &str) -> Result<Option<QuickAnswer>, SearchError> {
+    // Called before a query-level retry so the next attempt doesn't look like the failed one
+    fn rotate_identity(&self) {}
+
+    async fn quick_answer(&self, _query: &str) -> Result<Option<QuickAnswer>, SearchError> {
         Ok(None)
     }
 }
 
+// Headers shared by every engine, overridden by the engine's own `default_headers`
+fn request_headers(engine_headers: HeaderMap) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(ACCEPT, HeaderValue::from_static("text/html"));
+    headers.insert(ACCEPT_LANGUAGE, HeaderValue::from_static("fr-FR,fr;q=0.9"));
+    headers.extend(engine_headers);
+    headers
+}
+
+// Navigation headers a browser sends for a page typed in the address bar, or reached from
+// `referer`
+fn navigation_headers(referer: Option<&HeaderValue>) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    let site = match referer {
+        Some(referer) => {
+            headers.insert(REFERER, referer.clone());
+            "same-origin"
+        }
+        None => "none",
+    };
+    headers.insert("sec-fetch-dest", HeaderValue::from_static("document"));
+    headers.insert("sec-fetch-mode", HeaderValue::from_static("navigate"));
+    headers.insert("sec-fetch-site", HeaderValue::from_static(site));
+    headers.insert("sec-fetch-user", HeaderValue::from_static("?1"));
+    headers.insert(UPGRADE_INSECURE_REQUESTS, HeaderValue::from_static("1"));
+    headers
+}
+
+// `Referer` header for an engine's requests, `None` (no header) when unset or invalid
+fn referer_header(referer: Option<&str>) -> Option<HeaderValue> {
+    HeaderValue::from_str(referer?)
+        .map_err(|e| eprintln!("Invalid referer {:?}: {}", referer, e))
+        .ok()
+}
+
+// Absolute URL for a result link, which may be relative (`/path`), scheme-relative
+// (`//host/path`), one of Google's `/url?q=<target>` redirects or one of DuckDuckGo's
+// `//duckduckgo.com/l/?uddg=<target>` redirects, resolved against the page's `base` URL.
+// `None` for links that aren't http(s) once resolved.
+pub fn resolve_link(base: &str, href: &str) -> Option<String> {
+    let href = href.trim();
+    let base = Url::parse(base).ok()?;
+    let url = base.join(href).ok()?;
+
+    let host = url.host_str().unwrap_or_default();
+    let redirect_param = if url.path() == "/url" && url.host_str() == base.host_str() {
+        Some(["q", "url"].as_slice())
+    } else if url.path() == "/l/"
+        && (host == "duckduckgo.com" || host.ends_with(".duckduckgo.com"))
+    {
+        Some(["uddg"].as_slice())
+    } else {
+        None
+    };
+    if let Some(params) = redirect_param {
+        let target = url
+            .query_pairs()
+            .find(|(key, _)| params.contains(&key.as_ref()))
+            .map(|(_, target)| target.into_owned())?;
+        // The target is shorter than the redirect, so nested redirects bottom out
+        return resolve_link(base.as_str(), &target);
+    }
+
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+    // Absolute links are kept as written
+    Some(if href.starts_with("http") { href.to_string() } else { url.to_string() })
+}
+
+// Sponsored results link through Google's ad click tracking (`google.com/aclk`,
+// `googleadservices.com/pagead/aclk`). Organic links come back unchanged; ad links are `None`
+// with `drop_ads`, else unwrapped to the landing page in their `adurl`, or dropped when it's
+// missing.
+pub fn strip_ad_tracking(link: &str, drop_ads: bool) -> Option<String> {
+    let Ok(url) = Url::parse(link) else {
+        return Some(link.to_string());
+    };
+    let host = url.host_str().unwrap_or_default();
+    let is_ad = host == "googleadservices.com"
+        || host.ends_with(".googleadservices.com")
+        || (host.split('.').any(|label| label == "google") && url.path().starts_with("/aclk"));
+    if !is_ad {
+        return Some(link.to_string());
+    }
+    if drop_ads {
+        return None;
+    }
+
+    let landing_page = url
+        .query_pairs()
+        .find(|(key, _)| key == "adurl" || key == "url")
+        .map(|(_, target)| target.into_owned())?;
+    // Resolved like any other link, so a tracked landing page gets unwrapped in turn
+    resolve_link(link, &landing_page).and_then(|page| strip_ad_tracking(&page, drop_ads))
+}
+
+// Time behind a displayed result age, relative to `now`: "3 days ago", "il y a 3 jours",
+// "yesterday"/"hier", or an absolute "Mar 5, 2024" / "5 mars 2024" (taken as midnight UTC)
+pub fn parse_displayed_date(text: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let text = text.trim().to_lowercase();
+    if text == "yesterday" || text == "hier" {
+        return now.checked_sub_days(Days::new(1));
+    }
+
+    let relative = text
+        .strip_suffix(" ago")
+        .or_else(|| text.strip_prefix("il y a "))
+        .map(str::split_whitespace);
+    if let Some(mut words) = relative {
+        let count: u32 = words.next()?.parse().ok()?;
+        // Singular and plural alike; "mois" is both
+        let unit = words.next()?;
+        let unit = if unit == "mois" { unit } else { unit.trim_end_matches('s') };
+        return match unit {
+            "second" | "seconde" | "sec" => {
+                now.checked_sub_signed(TimeDelta::seconds(count.into()))
+            }
+            "minute" | "min" => now.checked_sub_signed(TimeDelta::minutes(count.into())),
+            "hour" | "heure" | "h" => now.checked_sub_signed(TimeDelta::hours(count.into())),
+            "day" | "jour" => now.checked_sub_days(Days::new(count.into())),
+            "week" | "semaine" => now.checked_sub_days(Days::new(7 * u64::from(count))),
+            "month" | "mois" => now.checked_sub_months(Months::new(count)),
+            "year" | "an" | "année" => now.checked_sub_months(Months::new(12 * count)),
+            _ => None,
+        };
+    }
+
+    NaiveDate::parse_from_str(&text, "%b %d, %Y")
+        .ok()
+        .or_else(|| parse_french_date(&text))
+        .map(|date| date.and_time(NaiveTime::MIN).and_utc())
+}
+
+// "5 mars 2024", "12 janv. 2023"
+fn parse_french_date(text: &str) -> Option<NaiveDate> {
+    const MONTHS: [&str; 12] = [
+        "janv", "févr", "mars", "avr", "mai", "juin",
+        "juil", "août", "sept", "oct", "nov", "déc",
+    ];
+
+    let mut words = text.split_whitespace();
+    let day = words.next()?.parse().ok()?;
+    let month = words.next()?.trim_end_matches('.');
+    let month = MONTHS.iter().position(|name| month.starts_with(name))? as u32 + 1;
+    let year = words.next()?.parse().ok()?;
+
+    NaiveDate::from_ymd_opt(year, month, day)
+}
+
 pub struct GoogleScraper {
-    client: reqwest::Client,
+    // Client and the user agent it sends; swapped for another one by `rotate_identity`
+    client: RwLock<(&'static str, reqwest::Client)>,
+    base_url: &'static str,
+    favicon_provider: FaviconProvider,
+    // Renders the page in a real browser when static parsing finds nothing
+    browser: Option<Arc<HeadlessBrowser>>,
+    // Sent as `Referer`, for engines that treat requests without a plausible one as bots
+    referer: Option<HeaderValue>,
+    // Leave out sponsored results instead of unwrapping them to their landing page
+    drop_ads: bool,
+    // `start` offset of the next page, keyed by request parameters and that page's number.
+    // Google often ignores `num=10` and returns 8 or 12 results, so offsets follow what each
+    // page actually held instead of assuming 10.
+    page_offsets: Mutex<HashMap<(String, u32), u32>>,
+}
+
+// Offsets remembered before the table is cleared, to keep it from growing unbounded
+const MAX_PAGE_OFFSETS: usize = 1024;
+
+impl Default for GoogleScraper {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl GoogleScraper {
     pub fn new() -> Self {
-        let client = reqwest::Client::builder()
-            .user_agent(*USER_AGENTS.choose(&mut rand::thread_rng()).unwrap())
+        let user_agent = *USER_AGENTS.choose(&mut rand::thread_rng()).unwrap();
+
+        Self {
+            client: RwLock::new((user_agent, Self::build_client(user_agent))),
+            base_url: base_url_override("GOOGLE_BASE_URL", "https://www.google.com/search"),
+            favicon_provider: FaviconProvider::default(),
+            browser: None,
+            referer: None,
+            drop_ads: true,
+            page_offsets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_favicon_provider(mut self, favicon_provider: FaviconProvider) -> Self {
+        self.favicon_provider = favicon_provider;
+        self
+    }
+
+    pub fn with_headless_browser(mut self, browser: Arc<HeadlessBrowser>) -> Self {
+        self.browser = Some(browser);
+        self
+    }
+
+    pub fn with_referer(mut self, referer: Option<&str>) -> Self {
+        self.referer = referer_header(referer);
+        self
+    }
+
+    pub fn with_drop_ads(mut self, drop_ads: bool) -> Self {
+        self.drop_ads = drop_ads;
+        self
+    }
+
+    fn build_client(user_agent: &str) -> reqwest::Client {
+        reqwest::Client::builder()
+            .user_agent(user_agent)
             .connect_timeout(Duration::from_secs(10))
             .timeout(Duration::from_secs(30))
             .build()
-            .unwrap();
+            .unwrap()
+    }
+
+    // Query string for a search, without the page offset. `date_range` (d, w, m, y) becomes
+    // `tbs=qdr:`, unless an absolute `date_filter` is set; `region` becomes `gl` and
+    // `language` `hl` (French when unset). Values that aren't two-letter codes are dropped.
+    pub fn search_params(
+        &self,
+        query: &str,
+        date_range: Option<&str>,
+        date_filter: &DateFilter,
+        region: Option<&str>,
+        language: Option<&str>,
+    ) -> String {
+        let is_code =
+            |value: &&str| value.len() == 2 && value.chars().all(|c| c.is_ascii_alphabetic());
+        let language = language.filter(is_code).unwrap_or("fr");
+        let mut params = format!(
+            "q={}&num=10&hl={}",
+            encode_query(query),
+            language.to_ascii_lowercase()
+        );
+
+        if let Some(region) = region.filter(is_code) {
+            params.push_str(&format!("&gl={}", region.to_ascii_lowercase()));
+        }
+
+        // Custom date range, dates as M/D/YYYY
+        if !date_filter.is_empty() {
+            let format = |date: Option<chrono::NaiveDate>| {
+                date.map(|date| date.format("%-m/%-d/%Y").to_string())
+                    .unwrap_or_default()
+            };
+            let tbs = format!(
+                "cdr:1,cd_min:{},cd_max:{}",
+                format(date_filter.after),
+                format(date_filter.before)
+            );
+            params.push_str(&format!("&tbs={}", encode_query(&tbs)));
+        } else if let Some(range) = date_range.filter(|d| matches!(*d, "d" | "w" | "m" | "y")) {
+            params.push_str(&format!("&tbs=qdr:{}", range));
+        }
 
-        Self { client }
+        params
     }
 
     async fn fetch_html(&self, url: &str) -> Result<String, SearchError> {
-        Ok(self
-            .client
+        let client = self.client.read().unwrap().1.clone();
+
+        let response = client
             .get(url)
-            .header("Accept", "text/html")
-            .header("Accept-Language", "fr-FR,fr;q=0.9")
+            .headers(request_headers(self.default_headers()))
             .send()
-            .await?
-            .text()
-            .await?)
+            .await?;
+
+        let final_url = response.url().clone();
+        let html = read_html(response).await?;
+        match Self::blocked_page(&final_url, &html) {
+            Some(reason) => Err(SearchError::Blocked(reason)),
+            None => Ok(html),
+        }
     }
 
     fn extract_favicon(&self, div: &scraper::ElementRef) -> Option<String> {
+        if self.favicon_provider == FaviconProvider::None {
+            return None;
+        }
+
         let favicon_selector = Selector::parse("img.XNo5Ab").unwrap();
         div.select(&favicon_selector)
             .next()
@@ -211,11 +776,9 @@ impl GoogleScraper {
                     .next()
                     .and_then(|a| a.value().attr("href"))
                     .and_then(|url| url::Url::parse(url).ok())
-                    .map(|url| {
-                        format!(
-                            "https://www.google.com/s2/favicons?domain={}",
-                            url.host_str().unwrap_or_default()
-                        )
+                    .and_then(|url| {
+                        self.favicon_provider
+                            .favicon_url(url.host_str().unwrap_or_default())
                     })
             })
     }
@@ -248,13 +811,155 @@ impl GoogleScraper {
                     })
                     .collect()
             })
-            .unwrap_or_else(|| vec![]);
+            .unwrap_or_default();
 
         (site_name, breadcrumbs)
     }
 
+    // Rating and price from the rich-snippet line, when Google shows one
+    fn extract_rich_metadata(&self, div: &scraper::ElementRef) -> (Option<f64>, Option<String>) {
+        let rating_selector = Selector::parse("span.yi40Hd").unwrap();
+        let stars_selector = Selector::parse("span.z3HNkc").unwrap();
+        let rich_line_selector = Selector::parse("div.fG8Fp span").unwrap();
+
+        // The numeric span is localized ("4,5"); the stars only carry an aria-label
+        let rating = div
+            .select(&rating_selector)
+            .next()
+            .map(|span| span.text().collect::<String>())
+            .or_else(|| {
+                div.select(&stars_selector)
+                    .next()
+                    .and_then(|span| span.value().attr("aria-label"))
+                    .map(String::from)
+            })
+            .and_then(|text| parse_rating(&text));
+
+        let price = div
+            .select(&rich_line_selector)
+            .map(|span| span.text().collect::<String>().trim().to_string())
+            .find(|text| looks_like_price(text));
+
+        (rating, price)
+    }
+
+    // Sitelinks block under prominent results; empty for ordinary results
+    fn extract_sitelinks(&self, div: &scraper::ElementRef) -> Vec<Sitelink> {
+        let sitelink_selector = Selector::parse("div.usJj9c a, table.jmjoTe a").unwrap();
+
+        div.select(&sitelink_selector)
+            .filter_map(|a| {
+                let link = resolve_link(self.base_url(), a.value().attr("href")?)?;
+                let title = a.text().collect::<String>().trim().to_string();
+
+                (!title.is_empty()).then_some(Sitelink { title, link })
+            })
+            .collect()
+    }
+
+    // Questions from the "People also ask" box; empty when Google doesn't show it
+    pub fn parse_people_also_ask(&self, html: &str) -> Vec<String> {
+        let document = Html::parse_document(html);
+        let question_selector = Selector::parse("div.related-question-pair").unwrap();
+
+        document
+            .select(&question_selector)
+            .filter_map(|pair| {
+                let question = pair
+                    .value()
+                    .attr("data-q")
+                    .map(String::from)
+                    .unwrap_or_else(|| pair.text().collect::<String>());
+                let question = decode_html_entities(question.trim());
+
+                (!question.is_empty()).then_some(question)
+            })
+            .collect()
+    }
+
+    // The answer box (`div.xpdopen` or a `block-component`); `None` when Google doesn't show one
+    pub fn parse_featured_snippet(&self, html: &str) -> Option<FeaturedSnippet> {
+        let document = Html::parse_document(html);
+        let box_selector = Selector::parse("div.xpdopen, block-component").unwrap();
+        let text_selector = Selector::parse(".hgKElc").unwrap();
+        let link_selector = Selector::parse("a[href^='http']").unwrap();
+        let title_selector = Selector::parse("h3").unwrap();
+
+        document.select(&box_selector).find_map(|answer_box| {
+            let text = answer_box.select(&text_selector).next()?.text().collect::<String>();
+            let link = answer_box.select(&link_selector).next()?.value().attr("href")?;
+            let source = answer_box
+                .select(&title_selector)
+                .next()
+                .map(|title| title.text().collect::<String>())
+                .unwrap_or_default();
+
+            let text = decode_html_entities(text.trim());
+            (!text.is_empty()).then(|| FeaturedSnippet {
+                text,
+                link: link.to_string(),
+                source: decode_html_entities(source.trim()),
+            })
+        })
+    }
+
+    // Organic results nested in the answer box are already covered by the featured snippet
+    fn in_featured_snippet(div: &scraper::ElementRef) -> bool {
+        div.ancestors()
+            .filter_map(scraper::ElementRef::wrap)
+            .map(|ancestor| ancestor.value())
+            .any(|element| {
+                element.name() == "block-component"
+                    || element.has_class("xpdopen", scraper::CaseSensitivity::CaseSensitive)
+            })
+    }
+
+    // `start` of the page after this one. Google's own "Next" link has it; without one, count
+    // every result container the page held, including the ads and answer box `parse_results`
+    // leaves out, since Google counts them too.
+    pub fn next_page_start(html: &str, start: u32) -> u32 {
+        let document = Html::parse_document(html);
+        let next_selector = Selector::parse("a#pnnext[href]").unwrap();
+        let div_selector = Selector::parse("div.g").unwrap();
+
+        document
+            .select(&next_selector)
+            .next()
+            .and_then(|next| next.value().attr("href"))
+            .and_then(|href| Url::parse("https://www.google.com").ok()?.join(href).ok())
+            .and_then(|next| {
+                next.query_pairs()
+                    .find(|(name, _)| name == "start")
+                    .and_then(|(_, value)| value.parse().ok())
+            })
+            .unwrap_or_else(|| start + document.select(&div_selector).count() as u32)
+    }
+
+    // Why `html`, fetched from `url` after redirects, is a "before you continue" consent page
+    // or a CAPTCHA rather than results. Those have no `div.g` and would otherwise look like a
+    // successful empty search.
+    pub fn blocked_page(url: &Url, html: &str) -> Option<String> {
+        if url.host_str() == Some("consent.google.com") {
+            return Some(format!("Google redirected to its consent page ({})", url));
+        }
+        if url.path().starts_with("/sorry/") {
+            return Some(format!("Google redirected to a CAPTCHA ({})", url));
+        }
+
+        let document = Html::parse_document(html);
+        let captcha_selector = Selector::parse("#captcha-form").unwrap();
+        let consent_selector = Selector::parse(r#"form[action*="consent.google.com"]"#).unwrap();
+        if document.select(&captcha_selector).next().is_some() {
+            Some("Google served a CAPTCHA".to_string())
+        } else if document.select(&consent_selector).next().is_some() {
+            Some("Google served its consent page".to_string())
+        } else {
+            None
+        }
+    }
+
     async fn extract_quick_answer(&self, query: &str) -> Result<Option<QuickAnswer>, SearchError> {
-        let url = format!("{}?q={}", self.base_url(), query);
+        let url = format!("{}?q={}", self.base_url(), encode_query(query));
         let html = self.fetch_html(&url).await?;
         let document = Html::parse_document(&html);
 
@@ -268,7 +973,9 @@ impl GoogleScraper {
             return Ok(Some(QuickAnswer::new_definition(
                 term.text().collect::<String>().trim().to_string(),
                 definition.text().collect::<String>().trim().to_string(),
-                Some("Google".to_string()),
+                self.name(),
+                // Google's own dictionary box
+                0.9,
             )));
         }
 
@@ -283,7 +990,15 @@ impl SearchEngine for GoogleScraper {
     }
 
     fn base_url(&self) -> &'static str {
-        "https://www.google.com/search"
+        self.base_url
+    }
+
+    fn default_headers(&self) -> HeaderMap {
+        navigation_headers(self.referer.as_ref())
+    }
+
+    fn supports_or(&self) -> bool {
+        true
     }
 
     async fn search(
@@ -291,19 +1006,64 @@ impl SearchEngine for GoogleScraper {
         query: &str,
         page: u32,
         date_range: Option<&str>,
+        date_filter: &DateFilter,
         region: Option<&str>,
         language: Option<&str>,
-    ) -> Result<Vec<SearchResult>, SearchError> {
-        let start = if page > 1 { (page - 1) * 10 } else { 0 };
-        let url = format!(
-            "{}?q={}&start={}&num=10&hl=fr",
-            self.base_url(),
-            query,
-            start
-        );
+    ) -> Result<SearchPage, SearchError> {
+        let params = self.search_params(query, date_range, date_filter, region, language);
 
-        let html = self.fetch_html(&url).await?;
-        Ok(self.parse_results(&html))
+        // Continue from where the previous page actually ended, when we fetched it
+        let start = if page > 1 {
+            let offsets = self.page_offsets.lock().unwrap();
+            offsets.get(&(params.clone(), page)).copied().unwrap_or((page - 1) * 10)
+        } else {
+            0
+        };
+        let url = format!("{}?{}&start={}", self.base_url(), params, start);
+
+        let mut html = self.fetch_html(&url).await?;
+        let mut results = self.parse_results(&html);
+
+        // Google increasingly renders results client-side; retry through a real browser. The
+        // static page was a valid answer, so a failed retry keeps it rather than failing the
+        // search.
+        if results.is_empty() {
+            if let Some(browser) = &self.browser {
+                match browser.fetch_rendered_html(&url).await {
+                    Ok(rendered) => {
+                        if let Some(reason) = Url::parse(&url)
+                            .ok()
+                            .and_then(|url| Self::blocked_page(&url, &rendered))
+                        {
+                            return Err(SearchError::Blocked(reason));
+                        }
+                        results = self.parse_results(&rendered);
+                        html = rendered;
+                    }
+                    Err(e) => eprintln!("Headless browser fallback failed for {}: {}", url, e),
+                }
+            }
+        }
+
+        if !results.is_empty() {
+            let mut offsets = self.page_offsets.lock().unwrap();
+            if offsets.len() >= MAX_PAGE_OFFSETS {
+                offsets.clear();
+            }
+            offsets.insert((params, page + 1), Self::next_page_start(&html, start));
+        }
+
+        let (people_also_ask, featured_snippet) = if page <= 1 {
+            (self.parse_people_also_ask(&html), self.parse_featured_snippet(&html))
+        } else {
+            (Vec::new(), None)
+        };
+
+        Ok(SearchPage {
+            results,
+            people_also_ask,
+            featured_snippet,
+        })
     }
 
     fn parse_results(&self, html: &str) -> Vec<SearchResult> {
@@ -312,9 +1072,11 @@ impl SearchEngine for GoogleScraper {
         let title_selector = Selector::parse("h3").unwrap();
         let link_selector = Selector::parse("a").unwrap();
         let snippet_selector = Selector::parse("div.VwiC3b").unwrap();
+        let age_selector = Selector::parse("span.MUxGbd.wuQ4Ob").unwrap();
 
         document
             .select(&div_selector)
+            .filter(|div| !Self::in_featured_snippet(div))
             .filter_map(|div| {
                 let title = div
                     .select(&title_selector)
@@ -322,16 +1084,9 @@ impl SearchEngine for GoogleScraper {
                     .text()
                     .collect::<String>();
 
-                let link = div
-                    .select(&link_selector)
-                    .next()?
-                    .value()
-                    .attr("href")?
-                    .to_string();
-
-                if !link.starts_with("http") {
-                    return None;
-                }
+                let href = div.select(&link_selector).next()?.value().attr("href")?;
+                let link = resolve_link(self.base_url(), href)?;
+                let link = strip_ad_tracking(&link, self.drop_ads)?;
 
                 let snippet = div
                     .select(&snippet_selector)
@@ -341,21 +1096,59 @@ impl SearchEngine for GoogleScraper {
 
                 let favicon_url = self.extract_favicon(&div);
                 let (site_name, breadcrumbs) = self.extract_site_info(&div);
+                let sitelinks = self.extract_sitelinks(&div);
+                let (rating, price) = self.extract_rich_metadata(&div);
+                let age_text = div
+                    .select(&age_selector)
+                    .next()
+                    .map(|age| age.text().collect::<String>())
+                    .map(|age| age.trim().trim_end_matches(['—', '-', '·']).trim().to_string())
+                    .filter(|age| !age.is_empty());
+                let publish_date = age_text
+                    .as_deref()
+                    .and_then(|age| parse_displayed_date(age, Utc::now()));
 
                 Some(SearchResult {
                     title,
                     link,
                     snippet,
                     source: self.name().to_string(),
+                    sources: Vec::new(),
                     score: 0.0,
+                    raw_score: None,
+                    flags: Vec::new(),
                     favicon_url,
                     site_name,
+                    display_url: Breadcrumb::display(&breadcrumbs),
                     breadcrumbs,
+                    sitelinks,
+                    rating,
+                    price,
+                    rank: 0,
+                    kind: None,
+                    age_text,
+                    publish_date,
                 })
             })
             .collect()
     }
 
+    fn rotate_identity(&self) {
+        let mut client = self.client.write().unwrap();
+        let current = client.0;
+        let others: Vec<&'static str> = USER_AGENTS
+            .iter()
+            .copied()
+            .filter(|&user_agent| user_agent != current)
+            .collect();
+        let user_agent = others
+            .choose(&mut rand::thread_rng())
+            .copied()
+            .unwrap_or(current);
+
+        *client = (user_agent, Self::build_client(user_agent));
+    }
+
     async fn quick_answer(&self, query: &str) -> Result<Option<QuickAnswer>, SearchError> {
         self.extract_quick_answer(query).await
     }
@@ -363,6 +1156,16 @@ impl SearchEngine for GoogleScraper {
 
 pub struct DuckDuckGoScraper {
     client: reqwest::Client,
+    base_url: &'static str,
+    favicon_provider: FaviconProvider,
+    // Sent as `Referer` when set
+    referer: Option<HeaderValue>,
+}
+
+impl Default for DuckDuckGoScraper {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl DuckDuckGoScraper {
@@ -374,20 +1177,56 @@ impl DuckDuckGoScraper {
             .build()
             .unwrap();
 
-        Self { client }
+        Self {
+            client,
+            base_url: base_url_override("DUCKDUCKGO_BASE_URL", "https://html.duckduckgo.com/html"),
+            favicon_provider: FaviconProvider::default(),
+            referer: None,
+        }
+    }
+
+    pub fn with_favicon_provider(mut self, favicon_provider: FaviconProvider) -> Self {
+        self.favicon_provider = favicon_provider;
+        self
+    }
+
+    pub fn with_referer(mut self, referer: Option<&str>) -> Self {
+        self.referer = referer_header(referer);
+        self
     }
 
     async fn fetch_html(&self, url: &str) -> Result<String, SearchError> {
-        Ok(self
+        let response = self
             .client
             .get(url)
-            .header("Accept", "text/html")
-            .header("Accept-Language", "fr-FR,fr;q=0.9")
+            .headers(request_headers(self.default_headers()))
             .send()
-            .await?
-            .text()
-            .await?)
+            .await?;
+
+        read_html(response).await
     }
+
+    // Build the search URL, mapping `date_range` to DuckDuckGo's `df` filter (d, w, m, y).
+    // Unknown values are ignored rather than forwarded.
+    pub fn build_url(&self, query: &str, page: u32, date_range: Option<&str>) -> String {
+        let mut url = if page <= 1 {
+            format!("{}?q={}", self.base_url(), encode_query(query))
+        } else {
+            format!(
+                "{}?q={}&s={}",
+                self.base_url(),
+                encode_query(query),
+                (page - 1) * 10
+            )
+        };
+
+        if let Some(df) = date_range.filter(|d| matches!(*d, "d" | "w" | "m" | "y")) {
+            url.push_str(&format!("&df={}", df));
+        }
+
+        url
+    }
+
     fn extract_favicon(&self, result: &scraper::ElementRef) -> Option<String> {
         let url = result
             .select(&Selector::parse(".result__url").unwrap())
@@ -395,7 +1234,8 @@ impl DuckDuckGoScraper {
             .map(|url| url.text().collect::<String>().trim().to_string())?;
 
         // DuckDuckGo n'affiche pas directement les favicons, on utilise donc un service tiers
-        Some(format!("https://www.google.com/s2/favicons?domain={}", url))
+        let domain = url.split('/').next().unwrap_or_default();
+        self.favicon_provider.favicon_url(domain)
     }
 
     fn extract_site_info(&self, result: &scraper::ElementRef) -> Vec<Breadcrumb> {
@@ -423,7 +1263,7 @@ impl DuckDuckGoScraper {
                     })
                     .collect()
             })
-            .unwrap_or_else(|| vec![]);
+            .unwrap_or_default();
 
         breadcrumbs
     }
@@ -436,7 +1276,18 @@ impl SearchEngine for DuckDuckGoScraper {
     }
 
     fn base_url(&self) -> &'static str {
-        "https://html.duckduckgo.com/html"
+        self.base_url
+    }
+
+    // From a privacy-minded browser
+    fn default_headers(&self) -> HeaderMap {
+        let mut headers = navigation_headers(self.referer.as_ref());
+        headers.insert(DNT, HeaderValue::from_static("1"));
+        headers
+    }
+
+    fn supports_or(&self) -> bool {
+        true
     }
 
     async fn search(
@@ -444,24 +1295,25 @@ impl SearchEngine for DuckDuckGoScraper {
         query: &str,
         page: u32,
         date_range: Option<&str>,
-        region: Option<&str>,
-        language: Option<&str>,
-    ) -> Result<Vec<SearchResult>, SearchError> {
-        let url = if page == 1 {
-            format!("{}?q={}", self.base_url(), query)
-        } else {
-            format!("{}?q={}&s={}", self.base_url(), query, (page - 1) * 10)
-        };
+        date_filter: &DateFilter,
+        _region: Option<&str>,
+        _language: Option<&str>,
+    ) -> Result<SearchPage, SearchError> {
+        // DuckDuckGo only has relative ranges, so `after:` is rounded up to the nearest one
+        let today = chrono::Utc::now().date_naive();
+        let date_range = date_range.or_else(|| date_filter.approximate_range(today));
+        let url = self.build_url(query, page, date_range);
 
         let html = self.fetch_html(&url).await?;
-        Ok(self.parse_results(&html))
+        Ok(self.parse_results(&html).into())
     }
 
     fn parse_results(&self, html: &str) -> Vec<SearchResult> {
         let document = Html::parse_document(html);
-        let result_selector = Selector::parse(".result").unwrap();
+        // Ads link through DuckDuckGo's click tracking (`y.js`) rather than to the page
+        let result_selector = Selector::parse(".result:not(.result--ad)").unwrap();
         let title_selector = Selector::parse(".result__title").unwrap();
-        let link_selector = Selector::parse(".result__url").unwrap();
+        let link_selector = Selector::parse("a.result__a[href]").unwrap();
         let snippet_selector = Selector::parse(".result__snippet").unwrap();
 
         document
@@ -473,35 +1325,413 @@ impl SearchEngine for DuckDuckGoScraper {
                     .text()
                     .collect::<String>();
 
-                let link = result
-                    .select(&link_selector)
+                let href = result.select(&link_selector).next()?.value().attr("href")?;
+                let link = resolve_link(self.base_url(), href)?;
+
+                let snippet = result
+                    .select(&snippet_selector)
+                    .next()
+                    .map(|s| s.text().collect::<String>())
+                    .unwrap_or_default();
+
+                let favicon_url = self.extract_favicon(&result);
+                let breadcrumbs = self.extract_site_info(&result);
+
+                Some(SearchResult {
+                    title: title.trim().to_string(),
+                    link,
+                    snippet: snippet.trim().to_string(),
+                    source: self.name().to_string(),
+                    sources: Vec::new(),
+                    score: 0.0,
+                    raw_score: None,
+                    flags: Vec::new(),
+                    favicon_url,
+                    site_name: None,
+                    display_url: Breadcrumb::display(&breadcrumbs),
+                    breadcrumbs,
+                    sitelinks: vec![],
+                    rating: None,
+                    price: None,
+                    rank: 0,
+                    kind: None,
+                    age_text: None,
+                    publish_date: None,
+                })
+            })
+            .collect()
+    }
+}
+pub struct BraveScraper {
+    client: reqwest::Client,
+    base_url: &'static str,
+    favicon_provider: FaviconProvider,
+    // Sent as `Referer` when set
+    referer: Option<HeaderValue>,
+}
+
+impl Default for BraveScraper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BraveScraper {
+    pub fn new() -> Self {
+        let client = reqwest::Client::builder()
+            .user_agent(*USER_AGENTS.choose(&mut rand::thread_rng()).unwrap())
+            .connect_timeout(Duration::from_secs(10))
+            .timeout(Duration::from_secs(30))
+            .build()
+            .unwrap();
+
+        Self {
+            client,
+            base_url: base_url_override("BRAVE_BASE_URL", "https://search.brave.com/search"),
+            favicon_provider: FaviconProvider::default(),
+            referer: None,
+        }
+    }
+
+    pub fn with_favicon_provider(mut self, favicon_provider: FaviconProvider) -> Self {
+        self.favicon_provider = favicon_provider;
+        self
+    }
+
+    pub fn with_referer(mut self, referer: Option<&str>) -> Self {
+        self.referer = referer_header(referer);
+        self
+    }
+
+    async fn fetch_html(&self, url: &str) -> Result<String, SearchError> {
+        let response = self
+            .client
+            .get(url)
+            .headers(request_headers(self.default_headers()))
+            .send()
+            .await?;
+
+        read_html(response).await
+    }
+
+    // Build the search URL. Brave pages by page index (`offset`) rather than result count, and
+    // `date_range` maps to its `tf` filter (pd, pw, pm, py).
+    pub fn build_url(&self, query: &str, page: u32, date_range: Option<&str>) -> String {
+        let mut url = format!("{}?q={}&source=web", self.base_url(), encode_query(query));
+        if page > 1 {
+            url.push_str(&format!("&offset={}", page - 1));
+        }
+
+        if let Some(tf) = date_range.filter(|d| matches!(*d, "d" | "w" | "m" | "y")) {
+            url.push_str(&format!("&tf=p{}", tf));
+        }
+
+        url
+    }
+
+    fn extract_favicon(&self, card: &scraper::ElementRef, link: &str) -> Option<String> {
+        if self.favicon_provider == FaviconProvider::None {
+            return None;
+        }
+
+        let favicon_selector = Selector::parse("img.favicon").unwrap();
+        card.select(&favicon_selector)
+            .next()
+            .and_then(|img| img.value().attr("src"))
+            .filter(|src| src.starts_with("http"))
+            .map(String::from)
+            .or_else(|| {
+                // Pas de favicon dans la carte : on passe par le service tiers
+                Url::parse(link).ok().and_then(|url| {
+                    self.favicon_provider.favicon_url(url.host_str().unwrap_or_default())
+                })
+            })
+    }
+
+    // Brave shows the URL as `domain › path › segments` in a `cite`
+    fn extract_site_info(&self, card: &scraper::ElementRef) -> (Option<String>, Vec<Breadcrumb>) {
+        let site_name_selector = Selector::parse(".sitename").unwrap();
+        let cite_selector = Selector::parse("cite").unwrap();
+
+        let site_name = card
+            .select(&site_name_selector)
+            .next()
+            .map(|name| name.text().collect::<String>().trim().to_string())
+            .filter(|name| !name.is_empty());
+
+        let breadcrumbs = card
+            .select(&cite_selector)
+            .next()
+            .map(|cite| Breadcrumb::parse_trail(&cite.text().collect::<String>()))
+            .unwrap_or_default();
+
+        (site_name, breadcrumbs)
+    }
+}
+
+#[async_trait]
+impl SearchEngine for BraveScraper {
+    fn name(&self) -> &'static str {
+        "Brave"
+    }
+
+    fn base_url(&self) -> &'static str {
+        self.base_url
+    }
+
+    fn default_headers(&self) -> HeaderMap {
+        navigation_headers(self.referer.as_ref())
+    }
+
+    async fn search(
+        &self,
+        query: &str,
+        page: u32,
+        date_range: Option<&str>,
+        date_filter: &DateFilter,
+        _region: Option<&str>,
+        _language: Option<&str>,
+    ) -> Result<SearchPage, SearchError> {
+        // Like DuckDuckGo, Brave only has relative ranges
+        let today = chrono::Utc::now().date_naive();
+        let date_range = date_range.or_else(|| date_filter.approximate_range(today));
+        let url = self.build_url(query, page, date_range);
+
+        let html = self.fetch_html(&url).await?;
+        Ok(self.parse_results(&html).into())
+    }
+
+    // Web results are `.snippet` cards with `data-type="web"`; news, videos and other
+    // verticals mixed into the page use other types and are skipped
+    fn parse_results(&self, html: &str) -> Vec<SearchResult> {
+        let document = Html::parse_document(html);
+        let card_selector = Selector::parse(".snippet[data-type=\"web\"]").unwrap();
+        let link_selector = Selector::parse("a[href]").unwrap();
+        let title_selector = Selector::parse(".title").unwrap();
+        let snippet_selector = Selector::parse(".snippet-description").unwrap();
+
+        document
+            .select(&card_selector)
+            .filter_map(|card| {
+                let href = card.select(&link_selector).next()?.value().attr("href")?;
+                let link = resolve_link(self.base_url(), href)?;
+
+                let title = card
+                    .select(&title_selector)
                     .next()?
                     .text()
                     .collect::<String>();
 
+                let snippet = card
+                    .select(&snippet_selector)
+                    .next()
+                    .map(|s| s.text().collect::<String>())
+                    .unwrap_or_default();
+
+                let favicon_url = self.extract_favicon(&card, &link);
+                let (site_name, breadcrumbs) = self.extract_site_info(&card);
+
+                Some(SearchResult {
+                    title: title.trim().to_string(),
+                    link,
+                    snippet: snippet.trim().to_string(),
+                    source: self.name().to_string(),
+                    sources: Vec::new(),
+                    score: 0.0,
+                    raw_score: None,
+                    flags: Vec::new(),
+                    favicon_url,
+                    site_name,
+                    display_url: Breadcrumb::display(&breadcrumbs),
+                    breadcrumbs,
+                    sitelinks: vec![],
+                    rating: None,
+                    price: None,
+                    rank: 0,
+                    kind: None,
+                    age_text: None,
+                    publish_date: None,
+                })
+            })
+            .collect()
+    }
+}
+
+pub struct StartpageScraper {
+    client: reqwest::Client,
+    base_url: &'static str,
+    favicon_provider: FaviconProvider,
+    // Sent as `Referer` when set
+    referer: Option<HeaderValue>,
+}
+
+impl Default for StartpageScraper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StartpageScraper {
+    pub fn new() -> Self {
+        let client = reqwest::Client::builder()
+            .user_agent(*USER_AGENTS.choose(&mut rand::thread_rng()).unwrap())
+            .connect_timeout(Duration::from_secs(10))
+            .timeout(Duration::from_secs(30))
+            .build()
+            .unwrap();
+
+        Self {
+            client,
+            base_url: base_url_override(
+                "STARTPAGE_BASE_URL",
+                "https://www.startpage.com/sp/search",
+            ),
+            favicon_provider: FaviconProvider::default(),
+            referer: None,
+        }
+    }
+
+    pub fn with_favicon_provider(mut self, favicon_provider: FaviconProvider) -> Self {
+        self.favicon_provider = favicon_provider;
+        self
+    }
+
+    pub fn with_referer(mut self, referer: Option<&str>) -> Self {
+        self.referer = referer_header(referer);
+        self
+    }
+
+    // Startpage takes searches as a POSTed form, like its own search box sends them
+    async fn fetch_html(&self, form: &[(&str, String)]) -> Result<String, SearchError> {
+        let response = self
+            .client
+            .post(self.base_url())
+            .headers(request_headers(self.default_headers()))
+            .form(form)
+            .send()
+            .await?;
+
+        read_html(response).await
+    }
+
+    // Form fields for a search. Pages are 1-based, and `date_range` maps to `with_date`
+    // (d, w, m, y); unknown values are ignored rather than forwarded.
+    pub fn form_params(
+        &self,
+        query: &str,
+        page: u32,
+        date_range: Option<&str>,
+    ) -> Vec<(&'static str, String)> {
+        let mut form = vec![
+            ("query", query.to_string()),
+            ("cat", "web".to_string()),
+            ("page", page.max(1).to_string()),
+        ];
+
+        if let Some(range) = date_range.filter(|d| matches!(*d, "d" | "w" | "m" | "y")) {
+            form.push(("with_date", range.to_string()));
+        }
+
+        form
+    }
+
+    // Startpage proxies no favicons, so they come from the favicon provider
+    fn extract_favicon(&self, link: &str) -> Option<String> {
+        let url = Url::parse(link).ok()?;
+        self.favicon_provider.favicon_url(url.host_str().unwrap_or_default())
+    }
+}
+
+#[async_trait]
+impl SearchEngine for StartpageScraper {
+    fn name(&self) -> &'static str {
+        "Startpage"
+    }
+
+    fn base_url(&self) -> &'static str {
+        self.base_url
+    }
+
+    fn default_headers(&self) -> HeaderMap {
+        navigation_headers(self.referer.as_ref())
+    }
+
+    async fn search(
+        &self,
+        query: &str,
+        page: u32,
+        date_range: Option<&str>,
+        date_filter: &DateFilter,
+        _region: Option<&str>,
+        _language: Option<&str>,
+    ) -> Result<SearchPage, SearchError> {
+        // Relative ranges only, like DuckDuckGo
+        let today = chrono::Utc::now().date_naive();
+        let date_range = date_range.or_else(|| date_filter.approximate_range(today));
+        let form = self.form_params(query, page, date_range);
+
+        let html = self.fetch_html(&form).await?;
+        Ok(self.parse_results(&html).into())
+    }
+
+    fn parse_results(&self, html: &str) -> Vec<SearchResult> {
+        let document = Html::parse_document(html);
+        let result_selector = Selector::parse(".w-gl__result").unwrap();
+        let title_selector = Selector::parse("a.w-gl__result-title").unwrap();
+        let url_selector = Selector::parse(".w-gl__result-url").unwrap();
+        let snippet_selector = Selector::parse(".w-gl__description").unwrap();
+
+        document
+            .select(&result_selector)
+            .filter_map(|result| {
+                let title_link = result.select(&title_selector).next()?;
+                let link = resolve_link(self.base_url(), title_link.value().attr("href")?)?;
+                let title = title_link.text().collect::<String>();
+
                 let snippet = result
                     .select(&snippet_selector)
                     .next()
                     .map(|s| s.text().collect::<String>())
                     .unwrap_or_default();
 
-                let favicon_url = self.extract_favicon(&result);
-                let breadcrumbs = self.extract_site_info(&result);
+                // Displayed as `https://example.com › docs › intro`
+                let breadcrumbs = result
+                    .select(&url_selector)
+                    .next()
+                    .map(|url| {
+                        let trail = url.text().collect::<String>();
+                        let trail = trail.trim();
+                        let trail = trail
+                            .strip_prefix("https://")
+                            .or_else(|| trail.strip_prefix("http://"))
+                            .unwrap_or(trail);
+                        Breadcrumb::parse_trail(trail)
+                    })
+                    .unwrap_or_default();
+                let favicon_url = self.extract_favicon(&link);
 
                 Some(SearchResult {
                     title: title.trim().to_string(),
-                    link: format!(
-                        "https://{}",
-                        link.trim_start_matches(|c: char| !c.is_alphanumeric())
-                    ),
+                    link,
                     snippet: snippet.trim().to_string(),
                     source: self.name().to_string(),
+                    sources: Vec::new(),
                     score: 0.0,
+                    raw_score: None,
+                    flags: Vec::new(),
                     favicon_url,
                     site_name: None,
+                    display_url: Breadcrumb::display(&breadcrumbs),
                     breadcrumbs,
+                    sitelinks: vec![],
+                    rating: None,
+                    price: None,
+                    rank: 0,
+                    kind: None,
+                    age_text: None,
+                    publish_date: None,
                 })
             })
             .collect()
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,280 @@
+use metrics::{
+    Counter, CounterFn, Gauge, GaugeFn, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder,
+    SharedString, Unit,
+};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use opentelemetry::metrics::{Meter, MeterProvider};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::metrics::{PeriodicReader, SdkMeterProvider};
+use opentelemetry_sdk::runtime;
+use opentelemetry_sdk::trace::TracerProvider;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use xxhash_rust::xxh3::xxh3_64;
+
+// Where metrics (and, for OTLP, tracing spans) are exported
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MetricsBackend {
+    // Metrics are recorded but go nowhere
+    #[default]
+    None,
+    // OpenTelemetry collector at `OTEL_EXPORTER_OTLP_ENDPOINT`
+    Otlp,
+    // Text exposition format at `GET /metrics`, for a Prometheus scraper
+    Prometheus,
+}
+
+impl FromStr for MetricsBackend {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "none" => Ok(MetricsBackend::None),
+            "otlp" => Ok(MetricsBackend::Otlp),
+            "prometheus" => Ok(MetricsBackend::Prometheus),
+            other => Err(format!("unknown metrics backend: {}", other)),
+        }
+    }
+}
+
+// How search queries appear in tracing spans
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QueryLogging {
+    Plain,
+    // A stable hash, enough to correlate requests without exposing the query
+    #[default]
+    Hashed,
+    Off,
+}
+
+impl QueryLogging {
+    // What to record for `query`. A client's `X-Do-Not-Log: 1` can only turn logging off,
+    // never back on, so the stricter of the two always wins.
+    pub fn loggable(&self, query: &str, do_not_log: bool) -> Option<String> {
+        match self {
+            _ if do_not_log => None,
+            QueryLogging::Plain => Some(query.to_string()),
+            QueryLogging::Hashed => Some(format!("{:016x}", xxh3_64(query.as_bytes()))),
+            QueryLogging::Off => None,
+        }
+    }
+}
+
+impl FromStr for QueryLogging {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "plain" => Ok(QueryLogging::Plain),
+            "hashed" => Ok(QueryLogging::Hashed),
+            "off" => Ok(QueryLogging::Off),
+            other => Err(format!("unknown query logging mode: {}", other)),
+        }
+    }
+}
+
+// Keeps the OTLP pipelines alive; flushes them when dropped
+pub struct Telemetry {
+    tracer_provider: TracerProvider,
+    meter_provider: SdkMeterProvider,
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        let _ = self.tracer_provider.shutdown();
+        let _ = self.meter_provider.shutdown();
+    }
+}
+
+// Record `metrics` for Prometheus; the handle renders them for `/metrics`. Histogram samples
+// are drained by a background upkeep task, so this must run inside the tokio runtime, and only
+// once.
+pub fn init_prometheus() -> Result<PrometheusHandle, String> {
+    let handle = PrometheusBuilder::new().install_recorder().map_err(|e| e.to_string())?;
+
+    let upkeep = handle.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(5));
+        loop {
+            interval.tick().await;
+            upkeep.run_upkeep();
+        }
+    });
+
+    Ok(handle)
+}
+
+// Export `tracing` spans and `metrics` counters/gauges/histograms to an OTLP collector over
+// gRPC. Must run inside the tokio runtime, and only once.
+pub fn init_otlp(endpoint: &str) -> Result<Telemetry, String> {
+    let span_exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let tracer_provider = TracerProvider::builder()
+        .with_batch_exporter(span_exporter, runtime::Tokio)
+        .build();
+
+    let metric_exporter = opentelemetry_otlp::MetricExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let meter_provider = SdkMeterProvider::builder()
+        .with_reader(PeriodicReader::builder(metric_exporter, runtime::Tokio).build())
+        .build();
+
+    tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer("search")))
+        .try_init()
+        .map_err(|e| e.to_string())?;
+    metrics::set_global_recorder(OtelRecorder::new(meter_provider.meter("search")))
+        .map_err(|e| e.to_string())?;
+
+    Ok(Telemetry {
+        tracer_provider,
+        meter_provider,
+    })
+}
+
+// `metrics` recorder forwarding every measurement to OpenTelemetry instruments.
+// Handles are cached per key so gauges keep their value between calls.
+struct OtelRecorder {
+    meter: Meter,
+    counters: Mutex<HashMap<Key, Counter>>,
+    gauges: Mutex<HashMap<Key, Gauge>>,
+    histograms: Mutex<HashMap<Key, Histogram>>,
+}
+
+impl OtelRecorder {
+    fn new(meter: Meter) -> Self {
+        Self {
+            meter,
+            counters: Mutex::new(HashMap::new()),
+            gauges: Mutex::new(HashMap::new()),
+            histograms: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+fn attributes(key: &Key) -> Vec<KeyValue> {
+    key.labels()
+        .map(|label| KeyValue::new(label.key().to_string(), label.value().to_string()))
+        .collect()
+}
+
+impl Recorder for OtelRecorder {
+    // Descriptions aren't forwarded, the instruments are created lazily on first use
+    fn describe_counter(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn describe_gauge(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn describe_histogram(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {
+    }
+
+    fn register_counter(&self, key: &Key, _metadata: &Metadata<'_>) -> Counter {
+        let mut counters = self.counters.lock().unwrap();
+        counters
+            .entry(key.clone())
+            .or_insert_with(|| {
+                Counter::from_arc(Arc::new(OtelCounter {
+                    counter: self.meter.u64_counter(key.name().to_string()).build(),
+                    attributes: attributes(key),
+                }))
+            })
+            .clone()
+    }
+
+    fn register_gauge(&self, key: &Key, _metadata: &Metadata<'_>) -> Gauge {
+        let mut gauges = self.gauges.lock().unwrap();
+        gauges
+            .entry(key.clone())
+            .or_insert_with(|| {
+                Gauge::from_arc(Arc::new(OtelGauge {
+                    gauge: self.meter.f64_gauge(key.name().to_string()).build(),
+                    attributes: attributes(key),
+                    value: AtomicU64::new(0f64.to_bits()),
+                }))
+            })
+            .clone()
+    }
+
+    fn register_histogram(&self, key: &Key, _metadata: &Metadata<'_>) -> Histogram {
+        let mut histograms = self.histograms.lock().unwrap();
+        histograms
+            .entry(key.clone())
+            .or_insert_with(|| {
+                Histogram::from_arc(Arc::new(OtelHistogram {
+                    histogram: self.meter.f64_histogram(key.name().to_string()).build(),
+                    attributes: attributes(key),
+                }))
+            })
+            .clone()
+    }
+}
+
+struct OtelCounter {
+    counter: opentelemetry::metrics::Counter<u64>,
+    attributes: Vec<KeyValue>,
+}
+
+impl CounterFn for OtelCounter {
+    fn increment(&self, value: u64) {
+        self.counter.add(value, &self.attributes);
+    }
+
+    // OTel counters only go up by deltas, absolute values have no equivalent
+    fn absolute(&self, _value: u64) {}
+}
+
+struct OtelGauge {
+    gauge: opentelemetry::metrics::Gauge<f64>,
+    attributes: Vec<KeyValue>,
+    // Current value as f64 bits, so increments and decrements can be turned into a reading
+    value: AtomicU64,
+}
+
+impl OtelGauge {
+    fn update(&self, update: impl Fn(f64) -> f64) {
+        let previous = self
+            .value
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |bits| {
+                Some(update(f64::from_bits(bits)).to_bits())
+            })
+            .unwrap_or_else(|bits| bits);
+        self.gauge.record(update(f64::from_bits(previous)), &self.attributes);
+    }
+}
+
+impl GaugeFn for OtelGauge {
+    fn increment(&self, value: f64) {
+        self.update(|current| current + value);
+    }
+
+    fn decrement(&self, value: f64) {
+        self.update(|current| current - value);
+    }
+
+    fn set(&self, value: f64) {
+        self.update(|_| value);
+    }
+}
+
+struct OtelHistogram {
+    histogram: opentelemetry::metrics::Histogram<f64>,
+    attributes: Vec<KeyValue>,
+}
+
+impl HistogramFn for OtelHistogram {
+    fn record(&self, value: f64) {
+        self.histogram.record(value, &self.attributes);
+    }
+}
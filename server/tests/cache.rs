@@ -0,0 +1,112 @@
+use search::cache::{Cache, CacheExt, InMemoryCache};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Entry {
+    query: String,
+    hits: u32,
+}
+
+fn entry() -> Entry {
+    Entry {
+        query: "rust async".to_string(),
+        hits: 3,
+    }
+}
+
+// Contract every `Cache` backend must honor, through `dyn Cache` as the server uses them. Keys
+// are prefixed so backends shared with other tests (a real Redis) don't collide.
+async fn round_trip(cache: &dyn Cache) {
+    cache.set("test:round-trip", &entry(), Duration::from_secs(60)).await.unwrap();
+
+    assert_eq!(cache.get::<Entry>("test:round-trip").await, Some(entry()));
+}
+
+async fn missing_key(cache: &dyn Cache) {
+    assert_eq!(cache.get::<Entry>("test:missing").await, None);
+}
+
+// A value that no longer deserializes is a miss, and is deleted so it can be repopulated
+async fn wrong_type(cache: &dyn Cache) {
+    cache.set("test:wrong-type", &"not an entry", Duration::from_secs(60)).await.unwrap();
+
+    assert_eq!(cache.get::<Entry>("test:wrong-type").await, None);
+    assert_eq!(cache.get::<String>("test:wrong-type").await, None);
+}
+
+async fn ttl_expiry(cache: &dyn Cache) {
+    // Redis TTLs have one-second granularity
+    cache.set("test:ttl", &entry(), Duration::from_secs(1)).await.unwrap();
+    assert_eq!(cache.get::<Entry>("test:ttl").await, Some(entry()));
+
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert_eq!(cache.get::<Entry>("test:ttl").await, None);
+}
+
+// A zero TTL is accepted, not an error, and the entry is gone right after
+async fn zero_ttl(cache: &dyn Cache) {
+    cache.set("test:zero-ttl", &entry(), Duration::ZERO).await.unwrap();
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert_eq!(cache.get::<Entry>("test:zero-ttl").await, None);
+}
+
+async fn flush(cache: &dyn Cache) {
+    cache.set("test:flush-a", &entry(), Duration::from_secs(60)).await.unwrap();
+    cache.set("test:flush-b", &entry(), Duration::from_secs(60)).await.unwrap();
+
+    cache.flush().await.unwrap();
+    assert_eq!(cache.get::<Entry>("test:flush-a").await, None);
+    assert_eq!(cache.get::<Entry>("test:flush-b").await, None);
+}
+
+async fn scan(cache: &dyn Cache) {
+    cache.set("test:scan:a", &entry(), Duration::from_secs(60)).await.unwrap();
+    cache.set("test:scan:b", &entry(), Duration::from_secs(60)).await.unwrap();
+    cache.set("test:other", &entry(), Duration::from_secs(60)).await.unwrap();
+
+    // Pages may come back empty before the end, so follow the cursor until it's 0
+    let mut keys = Vec::new();
+    let mut cursor = 0;
+    loop {
+        let (next, page) = cache.scan("test:scan:*", cursor).await.unwrap();
+        keys.extend(page);
+        cursor = next;
+        if cursor == 0 {
+            break;
+        }
+    }
+    keys.sort();
+
+    assert_eq!(keys, ["test:scan:a", "test:scan:b"]);
+}
+
+async fn conformance(cache: &dyn Cache) {
+    round_trip(cache).await;
+    missing_key(cache).await;
+    wrong_type(cache).await;
+    ttl_expiry(cache).await;
+    zero_ttl(cache).await;
+    scan(cache).await;
+    flush(cache).await;
+}
+
+#[tokio::test]
+async fn in_memory_cache_conforms() {
+    conformance(&InMemoryCache::new()).await;
+}
+
+#[cfg(feature = "redis-tests")]
+#[tokio::test]
+async fn redis_cache_conforms() {
+    use search::cache::RedisCache;
+    use testcontainers_modules::redis::Redis;
+    use testcontainers_modules::testcontainers::runners::AsyncRunner;
+
+    let container = Redis::default().start().await.unwrap();
+    let port = container.get_host_port_ipv4(6379).await.unwrap();
+    let cache = RedisCache::new(&format!("redis://127.0.0.1:{}", port)).await.unwrap();
+
+    conformance(&cache).await;
+}
@@ -0,0 +1,337 @@
+use chrono::{DateTime, TimeZone, Utc};
+use search::scoring::{
+    DedupPreference, DedupScope, DuplicateTitle, LowValuePatterns, ResultScorer, ScoringConfig,
+    TermWeights, TitleCopies,
+};
+use search::scraper::SearchResult;
+
+#[test]
+fn de_amp_unwraps_google_amp_urls() {
+    assert_eq!(
+        ResultScorer::de_amp("https://www.google.com/amp/s/www.example.com/news/article"),
+        Some("https://www.example.com/news/article".to_string())
+    );
+    assert_eq!(
+        ResultScorer::de_amp("https://www.google.fr/amp/example.com/page.amp.html"),
+        Some("http://example.com/page.amp.html".to_string())
+    );
+}
+
+#[test]
+fn de_amp_unwraps_ampproject_cache_urls() {
+    assert_eq!(
+        ResultScorer::de_amp("https://www-example-com.cdn.ampproject.org/c/s/www.example.com/a"),
+        Some("https://www.example.com/a".to_string())
+    );
+    assert_eq!(
+        ResultScorer::de_amp("https://example-com.cdn.ampproject.org/v/s/example.com/a?id=7"),
+        Some("https://example.com/a?id=7".to_string())
+    );
+}
+
+#[test]
+fn de_amp_leaves_other_urls_alone() {
+    assert_eq!(ResultScorer::de_amp("https://example.com/amp/article"), None);
+    assert_eq!(ResultScorer::de_amp("https://www.google.com/search?q=amp"), None);
+    assert_eq!(ResultScorer::de_amp("https://example-com.cdn.ampproject.org/"), None);
+    assert_eq!(ResultScorer::de_amp("not a url"), None);
+}
+
+fn date(y: i32, m: u32, d: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(y, m, d, 0, 0, 0).unwrap()
+}
+
+#[test]
+fn age_penalty_leaves_fresh_results_alone() {
+    let today = date(2024, 6, 1);
+    assert_eq!(ResultScorer::age_penalty(Some(today), today, 0.95), 1.0);
+    // A date after today (clock skew, misparse) isn't a bonus either
+    assert_eq!(ResultScorer::age_penalty(Some(date(2024, 6, 3)), today, 0.95), 1.0);
+}
+
+#[test]
+fn age_penalty_decays_with_years() {
+    let today = date(2024, 6, 1);
+    let one_year = ResultScorer::age_penalty(Some(date(2023, 6, 1)), today, 0.95);
+    assert!((one_year - 0.95).abs() < 0.001, "unexpected penalty: {}", one_year);
+
+    let five_years = ResultScorer::age_penalty(Some(date(2019, 6, 1)), today, 0.95);
+    assert!((five_years - 0.95_f64.powi(5)).abs() < 0.001, "unexpected penalty: {}", five_years);
+}
+
+#[test]
+fn dateless_results_are_not_penalized() {
+    assert_eq!(ResultScorer::age_penalty(None, date(2024, 6, 1), 0.95), 1.0);
+
+    let result = SearchResult {
+        title: "Rust async book".to_string(),
+        link: "https://rust-lang.github.io/async-book/".to_string(),
+        snippet: "Asynchronous programming in Rust, from futures to executors.".to_string(),
+        ..Default::default()
+    };
+    let dated = SearchResult {
+        publish_date: Some(date(2015, 1, 1)),
+        ..result.clone()
+    };
+    let scorer = ResultScorer::new(ScoringConfig::default());
+    let weights = TermWeights::default();
+
+    let undated = scorer.score_breakdown(&result, "rust async", &weights);
+    let dated = scorer.score_breakdown(&dated, "rust async", &weights);
+    assert!(dated.penalty < undated.penalty);
+    assert!(dated.total < undated.total);
+}
+
+#[test]
+fn breakdown_flags_name_the_adjustments_that_applied() {
+    let scorer = ResultScorer::new(ScoringConfig {
+        urls_blacklist: vec!["pinterest".to_string()],
+        relevant_urls: vec!["wikipedia".to_string()],
+        bonus_words: vec!["docs".to_string()],
+        ..Default::default()
+    });
+    let weights = TermWeights::default();
+    let flags = |link: &str, title: &str| {
+        let result = SearchResult {
+            title: title.to_string(),
+            link: link.to_string(),
+            ..Default::default()
+        };
+        scorer.score_breakdown(&result, "rust", &weights).flags
+    };
+
+    assert_eq!(
+        flags("https://fr.wikipedia.org/wiki/Rust", "Rust"),
+        ["https", "short_snippet", "relevant_domain", "exact_match"]
+    );
+    assert_eq!(
+        flags("http://www.pinterest.com/pin/rust", "Rust docs"),
+        ["short_snippet", "blacklisted", "bonus_word"]
+    );
+    assert_eq!(flags("http://example.com/rust", "Rust"), ["short_snippet", "exact_match"]);
+}
+
+#[test]
+fn breakdown_flags_cover_every_adjustment() {
+    let scorer = ResultScorer::new(ScoringConfig {
+        domain_match_bonus: 0.3,
+        position_weight: 0.5,
+        low_value: LowValuePatterns {
+            penalty: 0.7,
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    let result = SearchResult {
+        title: "Top 10 rust crates".to_string(),
+        link: "https://rust-crates.dev/tag/async".to_string(),
+        snippet: "A long enough snippet about the best rust crates of the year.".to_string(),
+        favicon_url: Some("https://rust-crates.dev/favicon.ico".to_string()),
+        publish_date: Some(Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap()),
+        rank: 2,
+        ..Default::default()
+    };
+    let flags = scorer.score_breakdown(&result, "rust", &TermWeights::default()).flags;
+
+    assert_eq!(
+        flags,
+        [
+            "https",
+            "low_value_title",
+            "low_value_path",
+            "old",
+            "position",
+            "domain_match",
+            "metadata"
+        ]
+    );
+
+    // The default low-value penalty changes nothing, so it isn't flagged
+    let default_flags = ResultScorer::new(ScoringConfig::default())
+        .score_breakdown(&result, "rust", &TermWeights::default())
+        .flags;
+    assert!(!default_flags.iter().any(|flag| flag.starts_with("low_value")));
+}
+
+#[test]
+fn domain_name_follows_the_public_suffix_list() {
+    let name = |link: &str| ResultScorer::domain_name(link);
+    assert_eq!(name("https://fr.react.dev/learn").as_deref(), Some("react"));
+    assert_eq!(name("https://news.bbc.co.uk/sport").as_deref(), Some("bbc"));
+    // Shared hosting suffixes name the site, not the host
+    assert_eq!(name("https://tokio-rs.github.io/axum").as_deref(), Some("tokio-rs"));
+    assert_eq!(name("https://rustblog.blogspot.com/").as_deref(), Some("rustblog"));
+    assert_eq!(name("http://127.0.0.1/"), None);
+}
+
+#[test]
+fn domain_match_needs_whole_words() {
+    let scorer = ResultScorer::new(ScoringConfig {
+        domain_match_bonus: 0.3,
+        ..Default::default()
+    });
+    let weights = TermWeights::default();
+    let domain_match = |link: &str, query: &str| {
+        let result = SearchResult {
+            link: link.to_string(),
+            ..Default::default()
+        };
+        scorer.score_breakdown(&result, query, &weights).domain_match
+    };
+
+    assert!(domain_match("https://react.dev/learn", "react hooks") > 0.0);
+    assert!(domain_match("https://www.rust-lang.org/", "rust traits") > 0.0);
+    assert!(domain_match("https://stackoverflow.com/q/1", "stack overflow") > 0.0);
+    assert_eq!(domain_match("https://react.dev/learn", "act"), 0.0);
+    assert_eq!(domain_match("https://example.com/react", "react"), 0.0);
+}
+
+fn section(link: &str, title: &str) -> SearchResult {
+    SearchResult {
+        title: title.to_string(),
+        link: link.to_string(),
+        snippet: format!("Snippet for {}", link),
+        ..Default::default()
+    }
+}
+
+fn dedup(results: Vec<SearchResult>, fragment_sensitive: bool) -> Vec<String> {
+    ResultScorer::remove_duplicates(
+        results,
+        DedupScope::Global,
+        fragment_sensitive,
+        TitleCopies::Merge,
+        None,
+        &DuplicateTitle::default(),
+        "tokio",
+    )
+    .into_iter()
+    .map(|result| result.link)
+    .collect()
+}
+
+#[test]
+fn dedup_ignores_fragments_by_default() {
+    let results = vec![
+        section("https://docs.rs/tokio#install", "Tokio install"),
+        section("https://docs.rs/tokio#usage", "Tokio usage"),
+    ];
+
+    assert_eq!(dedup(results, false), ["https://docs.rs/tokio#install"]);
+}
+
+#[test]
+fn fragment_sensitive_dedup_keeps_fragment_only_differences() {
+    // Sections of one page often share its title, which alone would merge them
+    let results = vec![
+        section("https://docs.rs/tokio#install", "Tokio"),
+        section("https://docs.rs/tokio#usage", "Tokio"),
+        section("https://docs.rs/tokio", "Tokio"),
+    ];
+
+    assert_eq!(
+        dedup(results, true),
+        [
+            "https://docs.rs/tokio#install",
+            "https://docs.rs/tokio#usage",
+            "https://docs.rs/tokio",
+        ]
+    );
+}
+
+#[test]
+fn fragment_sensitive_dedup_still_merges_same_fragment() {
+    let results = vec![
+        section("https://docs.rs/tokio#usage", "Tokio usage"),
+        section("https://www.docs.rs/tokio/#usage", "Usage - Tokio"),
+    ];
+
+    assert_eq!(dedup(results, true), ["https://docs.rs/tokio#usage"]);
+}
+
+// Deterministic mix of exact, near (one edit) and cross-host copies, shared titles and
+// snippets, `www.`/trailing-slash/fragment variants, across three engines
+fn dedup_fixture() -> Vec<SearchResult> {
+    let mut seed: u64 = 42;
+    let mut next = |bound: u64| {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (seed >> 33) % bound
+    };
+    let engines = ["Google", "DuckDuckGo", "Brave"];
+
+    (0..600)
+        .map(|index| {
+            let host = format!("site{}.example", next(80));
+            let mut path = format!("/docs/page-{}", next(200));
+            match next(6) {
+                0 => path.push('/'),
+                1 => path.push_str(&format!("#part-{}", next(3))),
+                2 => path.insert(3, 'x'),
+                _ => {}
+            }
+            let www = if next(4) == 0 { "www." } else { "" };
+            SearchResult {
+                title: format!("Page {} of the docs", next(500)),
+                link: format!("https://{}{}{}", www, host, path),
+                snippet: format!("Snippet number {} about the docs", next(2000)),
+                source: engines[index % 3].to_string(),
+                score: next(100) as f64 / 10.0,
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+// Scope, fragment-sensitive, title copies, engine preference, duplicate title
+type DedupScenario = (DedupScope, bool, TitleCopies, Option<DedupPreference>, DuplicateTitle);
+
+fn dedup_scenarios() -> Vec<DedupScenario> {
+    vec![
+        (DedupScope::Global, false, TitleCopies::Merge, None, DuplicateTitle::QueryTerms),
+        (DedupScope::Global, true, TitleCopies::Penalize, None, DuplicateTitle::Longest),
+        (
+            DedupScope::PerEngine,
+            false,
+            TitleCopies::Merge,
+            Some(DedupPreference { engine: "Brave".to_string(), margin: 2.0 }),
+            DuplicateTitle::Engine("DuckDuckGo".to_string()),
+        ),
+        (
+            DedupScope::Global,
+            true,
+            TitleCopies::Merge,
+            Some(DedupPreference { engine: "Google".to_string(), margin: 5.0 }),
+            DuplicateTitle::QueryTerms,
+        ),
+    ]
+}
+
+fn dedup_outputs() -> Vec<Vec<String>> {
+    dedup_scenarios()
+        .into_iter()
+        .map(|(scope, fragments, copies, preference, title)| {
+            ResultScorer::remove_duplicates(
+                dedup_fixture(),
+                scope,
+                fragments,
+                copies,
+                preference.as_ref(),
+                &title,
+                "page docs 12",
+            )
+            .into_iter()
+            .map(|r| format!("{} | {} | {} | {}", r.link, r.title, r.source, r.sources.join(",")))
+            .collect()
+        })
+        .collect()
+}
+
+// Outputs recorded from the pairwise implementation (every result against every kept one),
+// before candidates were narrowed down through `DedupIndex`
+#[test]
+fn indexed_dedup_matches_pairwise_outputs() {
+    let expected: Vec<Vec<String>> =
+        serde_json::from_str(include_str!("fixtures/dedup_expected.json")).unwrap();
+
+    assert_eq!(dedup_outputs(), expected);
+}
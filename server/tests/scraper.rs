@@ -0,0 +1,245 @@
+use chrono::NaiveDate;
+use search::query::DateFilter;
+use search::scraper::{
+    resolve_link, BraveScraper, DuckDuckGoScraper, GoogleScraper, SearchEngine, SearchResult,
+};
+use url::Url;
+
+#[test]
+fn duckduckgo_maps_date_range_to_df() {
+    let scraper = DuckDuckGoScraper::new();
+    let url = scraper.build_url("rust", 1, Some("w"));
+    assert!(url.contains("df=w"), "unexpected url: {}", url);
+}
+
+#[test]
+fn duckduckgo_ignores_unknown_date_range() {
+    let scraper = DuckDuckGoScraper::new();
+    let url = scraper.build_url("rust", 1, Some("decade"));
+    assert!(!url.contains("df="), "unexpected url: {}", url);
+}
+
+#[test]
+fn google_defaults_to_french_without_filters() {
+    let filter = DateFilter::default();
+    let params = GoogleScraper::new().search_params("rust", None, &filter, None, None);
+    assert_eq!(params, "q=rust&num=10&hl=fr");
+}
+
+#[test]
+fn google_maps_date_range_region_and_language() {
+    let params = GoogleScraper::new().search_params(
+        "rust",
+        Some("w"),
+        &DateFilter::default(),
+        Some("US"),
+        Some("en"),
+    );
+    assert_eq!(params, "q=rust&num=10&hl=en&gl=us&tbs=qdr:w");
+}
+
+#[test]
+fn google_drops_malformed_params() {
+    let params = GoogleScraper::new().search_params(
+        "rust",
+        Some("decade"),
+        &DateFilter::default(),
+        Some("U&S"),
+        Some("english"),
+    );
+    assert_eq!(params, "q=rust&num=10&hl=fr");
+}
+
+#[test]
+fn google_prefers_absolute_dates_over_date_range() {
+    let filter = DateFilter {
+        after: NaiveDate::from_ymd_opt(2024, 1, 15),
+        before: None,
+    };
+    let params = GoogleScraper::new().search_params("rust", Some("w"), &filter, None, None);
+    assert!(params.contains("&tbs=cdr"), "unexpected params: {}", params);
+    assert!(!params.contains("qdr"), "unexpected params: {}", params);
+}
+
+fn result(title: &str, link: &str, score: f64) -> SearchResult {
+    SearchResult {
+        title: title.to_string(),
+        link: link.to_string(),
+        score,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn ordering_ignores_score_noise_beyond_two_decimals() {
+    let a = result("Alpha", "https://a.example", 1.2345);
+    let b = result("Beta", "https://b.example", 1.2341);
+
+    // Both round to 1.23, so the title decides regardless of the raw float difference
+    let mut results = [b.clone(), a.clone()];
+    results.sort();
+    assert_eq!(results[0].title, "Alpha");
+
+    let mut results = [a, b];
+    results.sort();
+    assert_eq!(results[0].title, "Alpha");
+}
+
+const GOOGLE: &str = "https://www.google.com/search";
+
+#[test]
+fn resolve_link_keeps_absolute_links() {
+    assert_eq!(
+        resolve_link(GOOGLE, "https://example.com/a?b=c"),
+        Some("https://example.com/a?b=c".to_string())
+    );
+    assert_eq!(
+        resolve_link(GOOGLE, "http://example.com"),
+        Some("http://example.com".to_string())
+    );
+}
+
+#[test]
+fn resolve_link_resolves_relative_links_against_the_base() {
+    assert_eq!(
+        resolve_link(GOOGLE, "/maps/place/Paris"),
+        Some("https://www.google.com/maps/place/Paris".to_string())
+    );
+    assert_eq!(
+        resolve_link("https://html.duckduckgo.com/html/", "page?id=2"),
+        Some("https://html.duckduckgo.com/html/page?id=2".to_string())
+    );
+}
+
+#[test]
+fn resolve_link_resolves_scheme_relative_links() {
+    assert_eq!(
+        resolve_link(GOOGLE, "//example.com/path"),
+        Some("https://example.com/path".to_string())
+    );
+}
+
+#[test]
+fn resolve_link_unwraps_redirects() {
+    assert_eq!(
+        resolve_link(GOOGLE, "/url?q=https://example.com/a%3Fb%3Dc&sa=U"),
+        Some("https://example.com/a?b=c".to_string())
+    );
+    // Only the engine's own redirects: other sites' `/url` pages are ordinary results
+    assert_eq!(
+        resolve_link(GOOGLE, "https://example.com/url?q=https://other.example"),
+        Some("https://example.com/url?q=https://other.example".to_string())
+    );
+}
+
+#[test]
+fn resolve_link_unwraps_duckduckgo_redirects() {
+    let duckduckgo = "https://html.duckduckgo.com/html";
+    assert_eq!(
+        resolve_link(
+            duckduckgo,
+            "//duckduckgo.com/l/?uddg=http%3A%2F%2Fexample.com%2Fa%3Fb%3Dc&rut=0a1b2c"
+        ),
+        Some("http://example.com/a?b=c".to_string())
+    );
+    assert_eq!(
+        resolve_link(duckduckgo, "https://example.com/l/?uddg=https://other.example"),
+        Some("https://example.com/l/?uddg=https://other.example".to_string())
+    );
+}
+
+#[test]
+fn duckduckgo_links_to_the_result_href() {
+    let html = r##"<div class="result results_links web-result">
+        <h2 class="result__title"><a class="result__a"
+            href="//duckduckgo.com/l/?uddg=http%3A%2F%2Fold.example.org%2Fa%2Fvery%2Flong%2Fpath">
+            Old page</a></h2>
+        <a class="result__url" href="#">old.example.org/a/very/...</a>
+        <a class="result__snippet">A page only served over http.</a>
+    </div>
+    <div class="result result--ad">
+        <h2 class="result__title"><a class="result__a"
+            href="https://duckduckgo.com/y.js?ad_domain=ads.example">Ad</a></h2>
+        <a class="result__url" href="#">ads.example</a>
+    </div>"##;
+
+    let results = DuckDuckGoScraper::new().parse_results(html);
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].link, "http://old.example.org/a/very/long/path");
+}
+
+#[test]
+fn resolve_link_drops_non_http_links() {
+    assert_eq!(resolve_link(GOOGLE, "javascript:void(0)"), None);
+    assert_eq!(resolve_link(GOOGLE, "mailto:someone@example.com"), None);
+    assert_eq!(resolve_link(GOOGLE, "/url?sa=U"), None);
+}
+
+#[test]
+fn brave_parses_web_result_cards() {
+    let results = BraveScraper::new().parse_results(include_str!("fixtures/brave.html"));
+
+    // The news card between the two web results is skipped
+    assert_eq!(results.len(), 2);
+
+    let first = &results[0];
+    assert_eq!(first.title, "Getting Started - Asynchronous Programming in Rust");
+    assert_eq!(first.link, "https://rust-lang.github.io/async-book/");
+    assert!(first.snippet.starts_with("Welcome to Asynchronous Programming in Rust!"));
+    assert_eq!(first.source, "Brave");
+    assert_eq!(
+        first.favicon_url.as_deref(),
+        Some("https://imgs.search.brave.com/icon/rust-lang.github.io.png")
+    );
+    assert_eq!(first.site_name.as_deref(), Some("Rust Async Book"));
+    let crumbs: Vec<&str> = first.breadcrumbs.iter().map(|b| b.text.as_str()).collect();
+    assert_eq!(crumbs, ["rust-lang.github.io", "async-book"]);
+
+    // No favicon in the card: falls back to the favicon provider
+    let second = &results[1];
+    assert_eq!(second.link, "https://tokio.rs/tokio/tutorial");
+    assert_eq!(
+        second.favicon_url.as_deref(),
+        Some("https://www.google.com/s2/favicons?domain=tokio.rs")
+    );
+    let crumbs: Vec<&str> = second.breadcrumbs.iter().map(|b| b.text.as_str()).collect();
+    assert_eq!(crumbs, ["tokio.rs", "tokio", "tutorial"]);
+}
+
+#[test]
+fn brave_maps_page_and_date_range() {
+    let url = BraveScraper::new().build_url("rust", 3, Some("w"));
+    assert!(url.contains("offset=2"), "unexpected url: {}", url);
+    assert!(url.contains("tf=pw"), "unexpected url: {}", url);
+}
+
+#[test]
+fn google_detects_captcha_and_consent_pages() {
+    let sorry = Url::parse("https://www.google.com/sorry/index?continue=x").unwrap();
+    assert!(GoogleScraper::blocked_page(&sorry, "").is_some());
+
+    let consent = Url::parse("https://consent.google.com/ml?continue=x").unwrap();
+    assert!(GoogleScraper::blocked_page(&consent, "").is_some());
+
+    let search = Url::parse("https://www.google.com/search?q=rust").unwrap();
+    let captcha = r#"<html><body><form id="captcha-form" action="index"></form></body></html>"#;
+    assert!(GoogleScraper::blocked_page(&search, captcha).is_some());
+    let interstitial =
+        r#"<html><body><form action="https://consent.google.com/save"></form></body></html>"#;
+    assert!(GoogleScraper::blocked_page(&search, interstitial).is_some());
+
+    // A results page with nothing in it is just an empty search
+    assert_eq!(GoogleScraper::blocked_page(&search, "<html><body></body></html>"), None);
+}
+
+#[test]
+fn google_next_page_follows_its_next_link() {
+    let next = r#"<html><body><div class="g"></div>
+        <a id="pnnext" href="/search?q=rust&amp;start=9">Next</a></body></html>"#;
+    assert_eq!(GoogleScraper::next_page_start(next, 0), 9);
+
+    // Without one, every result container counts, even those left out of the results
+    let no_next = r#"<html><body><div class="g"></div><div class="g"></div>
+        <block-component><div class="g"></div></block-component></body></html>"#;
+    assert_eq!(GoogleScraper::next_page_start(no_next, 10), 13);
+}